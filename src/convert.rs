@@ -0,0 +1,95 @@
+use std::io::{self, Write};
+
+use crate::entry::LogEntry;
+
+pub const CSV_HEADER: &str =
+    "timestamp,timestamp_utc,month,day,time,hostname,process,pid,facility,severity,message";
+
+/// Writes `entry` as one line of newline-delimited JSON.
+pub fn write_ndjson(mut writer: impl Write, entry: &LogEntry) -> io::Result<()> {
+    serde_json::to_writer(&mut writer, entry)?;
+    writeln!(writer)
+}
+
+/// Writes `entry` as one CSV record matching [`CSV_HEADER`].
+pub fn write_csv(mut writer: impl Write, entry: &LogEntry) -> io::Result<()> {
+    writeln!(
+        writer,
+        "{},{},{},{},{},{},{},{},{},{},{}",
+        entry
+            .timestamp
+            .map(|timestamp| timestamp.format("%Y-%m-%dT%H:%M:%S").to_string())
+            .unwrap_or_default(),
+        entry
+            .timestamp_utc
+            .map(|timestamp| timestamp.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+            .unwrap_or_default(),
+        entry.month,
+        entry.day,
+        entry.time,
+        csv_field(&entry.hostname),
+        csv_field(&entry.process),
+        entry.pid,
+        entry
+            .priority
+            .map(|priority| priority.facility.name())
+            .unwrap_or_default(),
+        entry
+            .priority
+            .map(|priority| priority.severity.name())
+            .unwrap_or_default(),
+        csv_field(&entry.message)
+    )
+}
+
+/// Quotes a field when it contains a separator, quote or line break (RFC 4180).
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_quotes_fields_with_separators() {
+        let entry: LogEntry =
+            "Jul  4 23:22:09 host Microsoft Word[14463]: .sdef warning for argument '' of command \"x\", y"
+                .parse()
+                .unwrap();
+        let mut out = Vec::new();
+        write_csv(&mut out, &entry).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            ",,Jul,4,23:22:09,host,Microsoft Word,14463,,,\".sdef warning for argument '' of command \"\"x\"\", y\"\n"
+        );
+    }
+
+    #[test]
+    fn csv_includes_facility_and_severity() {
+        let entry: LogEntry = "<34>Jul  1 09:00:55 host sshd[512]: Failed password"
+            .parse()
+            .unwrap();
+        let mut out = Vec::new();
+        write_csv(&mut out, &entry).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            ",,Jul,1,09:00:55,host,sshd,512,auth,crit,Failed password\n"
+        );
+    }
+
+    #[test]
+    fn ndjson_uses_display_forms_for_month_and_time() {
+        let entry: LogEntry = "Jul  1 09:00:55 host kernel[0]: hello".parse().unwrap();
+        let mut out = Vec::new();
+        write_ndjson(&mut out, &entry).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"month\":\"Jul\",\"day\":1,\"time\":\"09:00:55\",\"hostname\":\"host\",\"process\":\"kernel\",\"pid\":0,\"message\":\"hello\",\"timestamp\":null,\"timestamp_utc\":null}\n"
+        );
+    }
+}
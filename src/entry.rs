@@ -0,0 +1,688 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, NaiveDateTime, Timelike, Utc};
+use serde::{Serialize, Serializer};
+
+use crate::error::{ParseError, ParseErrorKind};
+use crate::priority::{self, Priority};
+use crate::rfc5424::Rfc5424Fields;
+use crate::unified::UnifiedFields;
+
+/// One parsed line of a BSD/macOS syslog file:
+///
+/// ```text
+/// <Month> <Day> <Time> <Hostname> <Process>[PID]: <Message>
+/// ```
+///
+/// `<Process>` may contain spaces (`Microsoft Word[14463]:`).
+///
+/// The text fields borrow from the line they were parsed from where the
+/// format allows it, so parsing a line does not allocate; formats that
+/// unescape their fields (JSON) own them. [`LogEntry::into_owned`] detaches
+/// an entry from its line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LogEntry<'a> {
+    pub month: Month,
+    pub day: u8,
+    pub time: TimeOfDay,
+    pub hostname: Cow<'a, str>,
+    pub process: Cow<'a, str>,
+    pub pid: u32,
+    /// Everything after the `Process[PID]:` header, whitespace included.
+    pub message: Cow<'a, str>,
+    /// Full local date and time, filled in by [`crate::input::LineParser`]
+    /// once the year is known; `None` for a line parsed on its own.
+    pub timestamp: Option<NaiveDateTime>,
+    /// `timestamp` converted from the host's zone to UTC.
+    pub timestamp_utc: Option<DateTime<Utc>>,
+    /// Facility and severity from a leading `<PRI>`, when the line had one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    /// Extra header fields of an RFC 5424 line.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rfc5424: Option<Rfc5424Fields>,
+    /// Extra fields of an Apple unified log event.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unified: Option<UnifiedFields>,
+}
+
+impl LogEntry<'_> {
+    pub fn into_owned(self) -> LogEntry<'static> {
+        LogEntry {
+            month: self.month,
+            day: self.day,
+            time: self.time,
+            hostname: Cow::Owned(self.hostname.into_owned()),
+            process: Cow::Owned(self.process.into_owned()),
+            pid: self.pid,
+            message: Cow::Owned(self.message.into_owned()),
+            timestamp: self.timestamp,
+            timestamp_utc: self.timestamp_utc,
+            priority: self.priority,
+            rfc5424: self.rfc5424,
+            unified: self.unified,
+        }
+    }
+
+    /// Sets the entry's date and time from a full local timestamp.
+    pub fn set_local_time(&mut self, local: NaiveDateTime) {
+        self.month = Month::ALL[local.month0() as usize];
+        self.day = local.day() as u8;
+        self.time = TimeOfDay {
+            hour: local.hour() as u8,
+            minute: local.minute() as u8,
+            second: local.second() as u8,
+        };
+        self.timestamp = Some(local);
+    }
+}
+
+impl FromStr for LogEntry<'static> {
+    type Err = ParseError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        parse_log_entry(line).map(LogEntry::into_owned)
+    }
+}
+
+impl<'a> TryFrom<&'a str> for LogEntry<'a> {
+    type Error = ParseError;
+
+    fn try_from(line: &'a str) -> Result<Self, Self::Error> {
+        parse_log_entry(line)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Month {
+    Jan,
+    Feb,
+    Mar,
+    Apr,
+    May,
+    Jun,
+    Jul,
+    Aug,
+    Sep,
+    Oct,
+    Nov,
+    Dec,
+}
+
+impl Month {
+    pub const ALL: [Month; 12] = [
+        Month::Jan,
+        Month::Feb,
+        Month::Mar,
+        Month::Apr,
+        Month::May,
+        Month::Jun,
+        Month::Jul,
+        Month::Aug,
+        Month::Sep,
+        Month::Oct,
+        Month::Nov,
+        Month::Dec,
+    ];
+
+    /// 1 for January through 12 for December.
+    pub fn number(self) -> u32 {
+        self as u32 + 1
+    }
+
+    pub fn abbreviation(self) -> &'static str {
+        match self {
+            Month::Jan => "Jan",
+            Month::Feb => "Feb",
+            Month::Mar => "Mar",
+            Month::Apr => "Apr",
+            Month::May => "May",
+            Month::Jun => "Jun",
+            Month::Jul => "Jul",
+            Month::Aug => "Aug",
+            Month::Sep => "Sep",
+            Month::Oct => "Oct",
+            Month::Nov => "Nov",
+            Month::Dec => "Dec",
+        }
+    }
+}
+
+impl fmt::Display for Month {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.abbreviation())
+    }
+}
+
+impl Serialize for Month {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.abbreviation())
+    }
+}
+
+impl FromStr for Month {
+    type Err = ParseErrorKind;
+
+    fn from_str(month_str: &str) -> Result<Self, Self::Err> {
+        parse_month(month_str)
+    }
+}
+
+/// Wall-clock time of day as written in the log, without a date or zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimeOfDay {
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}:{:02}", self.hour, self.minute, self.second)
+    }
+}
+
+impl Serialize for TimeOfDay {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl FromStr for TimeOfDay {
+    type Err = ParseErrorKind;
+
+    fn from_str(time_str: &str) -> Result<Self, Self::Err> {
+        parse_time(time_str)
+    }
+}
+
+/// Parses a single line. Errors report line 1; callers reading a whole file
+/// attach the real line number with [`ParseError::with_line_number`].
+///
+/// A leading RFC 3164 `<PRI>`, as added by many relays (`<34>Jul  1 ...`),
+/// is decoded into [`LogEntry::priority`].
+pub fn parse_log_entry(input_record: &str) -> Result<LogEntry<'_>, ParseError> {
+    let (priority, record) = match priority::strip_priority(input_record) {
+        None => (None, input_record),
+        Some(Ok((priority, record))) => (Some(priority), record),
+        Some(Err(kind)) => return Err(ParseError::new(kind, 0)),
+    };
+    let prefix_len = input_record.len() - record.len();
+    let mut entry = parse_bsd_fields(record).map_err(|err| ParseError {
+        offset: err.offset + prefix_len,
+        ..err
+    })?;
+    entry.priority = priority;
+    Ok(entry)
+}
+
+fn parse_bsd_fields(input_record: &str) -> Result<LogEntry<'_>, ParseError> {
+    let mut parts = tokens(input_record);
+    let end = input_record.len();
+    let (offset, month_str) = parts.next().unwrap_or((end, ""));
+    let month = parse_month(month_str).map_err(|kind| ParseError::new(kind, offset))?;
+    let (offset, day_str) = parts.next().unwrap_or((end, ""));
+    let day = parse_day(day_str).map_err(|kind| ParseError::new(kind, offset))?;
+    let (offset, time_str) = parts.next().unwrap_or((end, ""));
+    let time = parse_time(time_str).map_err(|kind| ParseError::new(kind, offset))?;
+    let (_, hostname) = parts
+        .next()
+        .ok_or_else(|| ParseError::new(ParseErrorKind::MissingHostname, end))?;
+    let (offset, _) = parts
+        .next()
+        .ok_or_else(|| ParseError::new(ParseErrorKind::MissingProcess, end))?;
+    let (process, pid, message_str) = split_process_field(&input_record[offset..])
+        .and_then(|(process_field, message_str)| {
+            let (process, pid) = parse_process_name_and_pid(process_field)?;
+            Ok((process, pid, message_str))
+        })
+        .map_err(|kind| ParseError::new(kind, offset))?;
+    // Only the space separating the header from the message and a line
+    // terminator are dropped; the message keeps its own whitespace.
+    let message = message_str
+        .strip_prefix(' ')
+        .unwrap_or(message_str)
+        .trim_end_matches(['\n', '\r']);
+    Ok(LogEntry {
+        month,
+        day,
+        time,
+        hostname: Cow::Borrowed(hostname),
+        process: Cow::Borrowed(process),
+        pid,
+        message: Cow::Borrowed(message),
+        timestamp: None,
+        timestamp_utc: None,
+        priority: None,
+        rfc5424: None,
+        unified: None,
+    })
+}
+
+/// Recognises syslog's duplicate-suppression marker and returns N:
+///
+/// ```text
+/// --- last message repeated N times ---
+/// Jul  1 09:00:55 --- last message repeated N times ---
+/// Jul  1 09:00:55 host --- last message repeated N times ---
+/// Jul  1 09:00:55 host last message repeated N times
+/// <46>Jul  1 09:00:55 host last message repeated N times
+/// ```
+pub fn parse_repeat_marker(line: &str) -> Option<u32> {
+    const MARKER: &str = "last message repeated ";
+    let line = match priority::strip_priority(line) {
+        Some(Ok((_, rest))) => rest,
+        _ => line,
+    };
+    let start = line.find(MARKER)?;
+    let prefix = line[..start].trim_end();
+    let prefix = prefix.strip_suffix("---").unwrap_or(prefix);
+    let mut prefix_tokens = prefix.split_whitespace();
+    if let Some(month) = prefix_tokens.next() {
+        parse_month(month).ok()?;
+        if prefix_tokens.count() > 3 {
+            return None;
+        }
+    }
+    let mut rest = line[start + MARKER.len()..].split_whitespace();
+    let count = rest.next()?.parse::<u32>().ok()?;
+    if !matches!(rest.next(), Some("time" | "times")) {
+        return None;
+    }
+    match rest.next() {
+        None | Some("---") if rest.next().is_none() => Some(count),
+        _ => None,
+    }
+}
+
+/// Month, day and time that open a repeat marker, when it has them, as in
+/// `Jul  1 09:00:58 --- last message repeated 4 times ---`.
+pub fn repeat_marker_time(line: &str) -> Option<(Month, u8, TimeOfDay)> {
+    let line = match priority::strip_priority(line) {
+        Some(Ok((_, rest))) => rest,
+        _ => line,
+    };
+    let mut parts = line.split_whitespace();
+    let month = parse_month(parts.next()?).ok()?;
+    let day = parse_day(parts.next()?).ok()?;
+    let time = parse_time(parts.next()?).ok()?;
+    Some((month, day, time))
+}
+
+/// Whether `line` opens with a month, after an optional `<PRI>`, as every
+/// BSD entry does. Lines that do not are continuations of the one before.
+pub fn starts_with_month(line: &str) -> bool {
+    let line = match priority::strip_priority(line) {
+        Some(Ok((_, rest))) => rest,
+        _ => line,
+    };
+    line.split_whitespace()
+        .next()
+        .is_some_and(|token| parse_month(token).is_ok())
+}
+
+/// Whitespace-separated tokens paired with their byte offset in `line`.
+fn tokens(line: &str) -> impl Iterator<Item = (usize, &str)> {
+    line.split_whitespace()
+        .map(move |token| (token.as_ptr() as usize - line.as_ptr() as usize, token))
+}
+
+/// Splits `rest` (everything after the hostname) into the `Process Name[PID]:`
+/// field and the message that follows it. The process name runs up to the
+/// first `[`, so names such as `Microsoft Word` or `Google Chrome Helper` may
+/// contain spaces; a `: ` seen before any `[` means the pid is missing.
+fn split_process_field(rest: &str) -> Result<(&str, &str), ParseErrorKind> {
+    let missing_pid = |end: usize| ParseErrorKind::MissingPid(rest[..end].trim_end().to_string());
+    let field_end = rest
+        .match_indices(':')
+        .map(|(index, _)| index)
+        .find(|&index| {
+            rest[index + 1..]
+                .chars()
+                .next()
+                .is_none_or(char::is_whitespace)
+        })
+        .unwrap_or(rest.len());
+    let open = rest[..field_end]
+        .find('[')
+        .ok_or_else(|| missing_pid(field_end))?;
+    let close = rest[open..]
+        .find(']')
+        .map(|index| open + index + 1)
+        .ok_or_else(|| missing_pid(rest.len()))?;
+    let end = if rest[close..].starts_with(':') {
+        close + 1
+    } else {
+        close
+    };
+    Ok((&rest[..end], &rest[end..]))
+}
+
+fn parse_process_name_and_pid(process_name_str: &str) -> Result<(&str, u32), ParseErrorKind> {
+    let missing_pid = || ParseErrorKind::MissingPid(process_name_str.to_string());
+    let (name, pid_field) = process_name_str.split_once('[').ok_or_else(missing_pid)?;
+    if pid_field.contains('[') {
+        return Err(missing_pid());
+    }
+    let pid_str = pid_field
+        .strip_suffix("]:")
+        .or_else(|| pid_field.strip_suffix(']'))
+        .ok_or_else(missing_pid)?;
+    let pid = pid_str
+        .parse::<u32>()
+        .map_err(|_| ParseErrorKind::InvalidPid(process_name_str.to_string()))?;
+    Ok((name.trim_end(), pid))
+}
+
+fn parse_time(timestamp_str: &str) -> Result<TimeOfDay, ParseErrorKind> {
+    let invalid = || ParseErrorKind::InvalidTime(timestamp_str.to_string());
+    let mut parts = timestamp_str.split(':');
+    let mut fields = [0u8; 3];
+    for field in &mut fields {
+        let part = parts.next().ok_or_else(invalid)?;
+        if part.len() != 2 {
+            return Err(invalid());
+        }
+        *field = part.parse::<u8>().map_err(|_| invalid())?;
+    }
+    if parts.next().is_some() {
+        return Err(invalid());
+    }
+    let [hour, minute, second] = fields;
+    if hour < 24 && minute < 60 && second < 60 {
+        Ok(TimeOfDay {
+            hour,
+            minute,
+            second,
+        })
+    } else {
+        Err(invalid())
+    }
+}
+
+fn parse_month(month_str: &str) -> Result<Month, ParseErrorKind> {
+    Month::ALL
+        .into_iter()
+        .find(|month| month.abbreviation().eq_ignore_ascii_case(month_str))
+        .ok_or_else(|| ParseErrorKind::InvalidMonth(month_str.to_string()))
+}
+
+fn parse_day(day_str: &str) -> Result<u8, ParseErrorKind> {
+    let invalid = || ParseErrorKind::InvalidDay(day_str.to_string());
+    let day = day_str.parse::<u8>().map_err(|_| invalid())?;
+    if day > 0 && day <= 31 {
+        Ok(day)
+    } else {
+        Err(invalid())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_a_valid_month() {
+        assert_eq!(parse_month("Jul").unwrap(), Month::Jul);
+    }
+
+    #[test]
+    fn parse_an_invalid_month() {
+        assert!(parse_month("xxx").is_err());
+    }
+
+    #[test]
+    fn parse_a_valid_day() {
+        assert_eq!(parse_day("1").unwrap(), 1);
+    }
+
+    #[test]
+    fn parse_an_invalid_day() {
+        assert!(parse_day("0").is_err());
+    }
+
+    #[test]
+    fn parse_a_valid_time() {
+        assert_eq!(
+            parse_time("09:01:05").unwrap(),
+            TimeOfDay {
+                hour: 9,
+                minute: 1,
+                second: 5
+            }
+        );
+        assert_eq!(parse_time("09:01:05").unwrap().to_string(), "09:01:05");
+    }
+
+    #[test]
+    fn parse_an_invalid_time() {
+        assert!(parse_time("9:01:05").is_err());
+        assert!(parse_time("24:00:00").is_err());
+    }
+
+    #[test]
+    fn parse_a_valid_process_name() {
+        assert_eq!(
+            parse_process_name_and_pid("com.apple.CDScheduler[43]:").unwrap(),
+            ("com.apple.CDScheduler", 43)
+        );
+    }
+
+    #[test]
+    fn parse_a_process_name_followed_by_detail() {
+        assert_eq!(
+            parse_process_name_and_pid("sandboxd[129]").unwrap(),
+            ("sandboxd", 129)
+        );
+    }
+
+    #[test]
+    fn parse_a_process_name_without_pid() {
+        assert!(parse_process_name_and_pid("com.apple.CDScheduler[]:").is_err());
+    }
+
+    #[test]
+    fn errors_name_the_field_and_offset() {
+        let err = parse_log_entry("Jul 32 09:01:05 host kernel[0]: x").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidDay("32".to_string()));
+        assert_eq!(err.offset, 4);
+
+        let err = parse_log_entry("Jul  1 09:01:05 host Microsoft Word: x").unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::MissingPid("Microsoft Word".to_string())
+        );
+        assert_eq!(err.offset, 21);
+
+        let err = parse_log_entry("Jul  1 09:01:05").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::MissingHostname);
+        assert_eq!(err.offset, 15);
+
+        let err = parse_log_entry("").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidMonth(String::new()));
+    }
+
+    #[test]
+    fn errors_report_line_number() {
+        let err = parse_log_entry("xxx").unwrap_err().with_line_number(42);
+        assert_eq!(err.to_string(), "line 42, byte 0: invalid month: \"xxx\"");
+    }
+
+    #[test]
+    fn parse_process_names_with_spaces() {
+        let entry: LogEntry = "Jul  4 23:22:09 calvisitor-10-105-162-105 Microsoft Word[14463]: Cocoa scripting error for '0x00660011'"
+            .parse()
+            .unwrap();
+        assert_eq!(entry.process, "Microsoft Word");
+        assert_eq!(entry.pid, 14463);
+        assert_eq!(entry.message, "Cocoa scripting error for '0x00660011'");
+
+        let entry: LogEntry =
+            "Jul  3 17:10:11 calvisitor-10-105-160-184 BezelServices 255.10[94]: ASSERTION FAILED"
+                .parse()
+                .unwrap();
+        assert_eq!(entry.process, "BezelServices 255.10");
+        assert_eq!(entry.pid, 94);
+    }
+
+    #[test]
+    fn brackets_in_the_message_are_not_a_pid() {
+        let err = parse_log_entry("Jul  1 09:01:05 host configd: state [1] changed").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::MissingPid("configd".to_string()));
+    }
+
+    #[test]
+    fn split_process_field_keeps_detail_in_message() {
+        assert_eq!(
+            split_process_field("sandboxd[129] ([31211]): deny network-outbound").unwrap(),
+            ("sandboxd[129]", " ([31211]): deny network-outbound")
+        );
+    }
+
+    #[test]
+    fn parse_repeat_markers() {
+        assert_eq!(
+            parse_repeat_marker("--- last message repeated 3 times ---"),
+            Some(3)
+        );
+        assert_eq!(
+            parse_repeat_marker("Jul  1 09:00:55 --- last message repeated 1 time ---"),
+            Some(1)
+        );
+        assert_eq!(
+            parse_repeat_marker(
+                "Jul  1 09:00:55 calvisitor-10-105-160-95 --- last message repeated 12 times ---"
+            ),
+            Some(12)
+        );
+        assert_eq!(
+            parse_repeat_marker("Jul  1 09:00:55 buildbox last message repeated 2 times"),
+            Some(2)
+        );
+        assert_eq!(
+            parse_repeat_marker("<46>Jul  1 09:00:55 buildbox last message repeated 4 times"),
+            Some(4)
+        );
+        assert_eq!(
+            parse_repeat_marker(
+                "Jul  1 09:00:55 host kernel[0]: last message repeated 2 times in a row"
+            ),
+            None
+        );
+        assert_eq!(
+            parse_repeat_marker("Jul  1 09:00:55 host kernel[0]: last message repeated 2 times"),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_a_valid_line() {
+        let line = "Jul  1 09:01:05 calvisitor-10-105-160-95 com.apple.CDScheduler[43]: Thermal pressure state: 1 Memory pressure state: 0";
+        let entry = parse_log_entry(line).unwrap();
+        assert_eq!(entry.month, Month::Jul);
+        assert_eq!(entry.day, 1);
+        assert_eq!(entry.time.to_string(), "09:01:05");
+        assert_eq!(entry.hostname, "calvisitor-10-105-160-95");
+        assert_eq!(entry.process, "com.apple.CDScheduler");
+        assert_eq!(entry.pid, 43);
+        assert_eq!(
+            entry.message,
+            "Thermal pressure state: 1 Memory pressure state: 0"
+        );
+    }
+
+    #[test]
+    fn parse_another_valid_line() {
+        let line = "Jul  8 06:11:46 calvisitor-10-105-162-124 WindowServer[184]: send_datagram_available_ping: pid 445 failed to act on a ping it dequeued before timing out.
+";
+        let entry = parse_log_entry(line).unwrap();
+        assert_eq!(entry.month, Month::Jul);
+        assert_eq!(entry.day, 8);
+        assert_eq!(entry.time.to_string(), "06:11:46");
+        assert_eq!(entry.hostname, "calvisitor-10-105-162-124");
+        assert_eq!(entry.process, "WindowServer");
+        assert_eq!(entry.pid, 184);
+        assert_eq!(
+            entry.message,
+            "send_datagram_available_ping: pid 445 failed to act on a ping it dequeued before timing out."
+        );
+    }
+
+    #[test]
+    fn parse_a_line_with_a_priority_prefix() {
+        let line = "<34>Jul  1 09:00:55 calvisitor-10-105-160-95 sshd[512]: Failed password";
+        let entry = parse_log_entry(line).unwrap();
+        let priority = entry.priority.unwrap();
+        assert_eq!(priority.facility, crate::priority::Facility::Auth);
+        assert_eq!(priority.severity, crate::priority::Severity::Crit);
+        assert_eq!(entry.month, Month::Jul);
+        assert_eq!(entry.process, "sshd");
+        assert_eq!(entry.message, "Failed password");
+        assert_eq!(parse_log_entry(&line[4..]).unwrap().priority, None);
+
+        let err = parse_log_entry("<34>Jly  1 09:00:55 host sshd[512]: x").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidMonth("Jly".to_string()));
+        assert_eq!(err.offset, 4);
+        let err = parse_log_entry("<999>Jul  1 09:00:55 host sshd[512]: x").unwrap_err();
+        assert_eq!(err.kind.name(), "invalid_priority");
+    }
+
+    #[test]
+    fn lines_starting_with_a_month() {
+        assert!(starts_with_month("Jul  1 09:00:55 host kernel[0]: x"));
+        assert!(starts_with_month("<34>Jul  1 09:00:55 host sshd[1]: x"));
+        assert!(!starts_with_month(
+            "    0   com.apple.kernel  0xffffff7f80e4a000"
+        ));
+        assert!(!starts_with_month("Thread 0 Crashed:"));
+        assert!(!starts_with_month(""));
+    }
+
+    #[test]
+    fn parse_via_from_str_and_try_from() {
+        let line = "Jul  1 09:00:55 calvisitor-10-105-160-95 kernel[0]: AppleThunderboltNHIType2::prePCIWake - power up complete - took 2 us";
+        let from_str: LogEntry = line.parse().unwrap();
+        let try_from = LogEntry::try_from(line).unwrap();
+        assert_eq!(from_str, try_from);
+        assert_eq!(from_str.process, "kernel");
+        assert_eq!(from_str.pid, 0);
+    }
+
+    #[test]
+    fn parsed_fields_borrow_the_line() {
+        let line = "Jul  1 09:00:55 host kernel[0]: done";
+        let entry = parse_log_entry(line).unwrap();
+        assert!(matches!(entry.hostname, Cow::Borrowed(_)));
+        assert!(matches!(entry.process, Cow::Borrowed(_)));
+        assert!(matches!(entry.message, Cow::Borrowed(_)));
+
+        let owned: LogEntry<'static> = entry.clone().into_owned();
+        assert_eq!(owned, entry);
+        assert!(matches!(owned.message, Cow::Owned(_)));
+    }
+
+    #[test]
+    fn message_keeps_its_whitespace() {
+        let entry =
+            parse_log_entry("Jul  1 09:00:55 host kernel[0]:   indented\tby  tabs \r\n").unwrap();
+        assert_eq!(entry.message, "  indented\tby  tabs ");
+    }
+
+    #[test]
+    fn test_split_into_flds_str() {
+        let input_record = "Jul  1 09:01:05 calvisitor-10-105-160-95 com.apple.CDScheduler[43]: Thermal pressure state: 1 Memory pressure state: 0";
+        let entry = parse_log_entry(input_record).unwrap();
+        assert_eq!(entry.month.to_string(), "Jul");
+        assert_eq!(entry.day, 1);
+        assert_eq!(entry.time.to_string(), "09:01:05");
+        assert_eq!(entry.hostname, "calvisitor-10-105-160-95");
+        assert_eq!(entry.process, "com.apple.CDScheduler");
+        assert_eq!(entry.pid, 43);
+        assert_eq!(
+            entry.message,
+            "Thermal pressure state: 1 Memory pressure state: 0"
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use std::error::Error;
+use std::fmt;
+
+/// Why a line could not be parsed, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// 1-based line number within the source the line was read from.
+    pub line_number: usize,
+    /// Byte offset of the offending field within the line.
+    pub offset: usize,
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, offset: usize) -> Self {
+        Self {
+            kind,
+            line_number: 1,
+            offset,
+        }
+    }
+
+    pub fn with_line_number(mut self, line_number: usize) -> Self {
+        self.line_number = line_number;
+        self
+    }
+
+    /// A line that is not the JSON object a format expected, at the column
+    /// serde_json reports; the position it appends to its message is dropped.
+    pub(crate) fn from_json(err: serde_json::Error) -> Self {
+        let message = err.to_string();
+        let message = message
+            .rsplit_once(" at line ")
+            .map_or(message.as_str(), |(message, _)| message);
+        Self::new(
+            ParseErrorKind::InvalidJson(message.to_string()),
+            err.column().saturating_sub(1),
+        )
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, byte {}: {}",
+            self.line_number, self.offset, self.kind
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+/// One variant per field of a log line. Variants carrying a `String` hold
+/// the token that was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    InvalidMonth(String),
+    InvalidDay(String),
+    InvalidTime(String),
+    MissingHostname,
+    MissingProcess,
+    MissingPid(String),
+    InvalidPid(String),
+    /// A `last message repeated N times` marker with no entry before it.
+    OrphanedRepeat,
+    InvalidPriority(String),
+    InvalidVersion(String),
+    InvalidTimestamp(String),
+    InvalidStructuredData(String),
+    /// A user-defined pattern's literal text, or the end of the line, was
+    /// not where the pattern expected it.
+    PatternMismatch(String),
+    /// A structured record with no time to place it at.
+    MissingTimestamp,
+    /// A JSON line that is malformed or has a field of the wrong type.
+    InvalidJson(String),
+}
+
+impl ParseErrorKind {
+    /// Stable snake_case name used as the key in the summary's rejection counts.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ParseErrorKind::InvalidMonth(_) => "invalid_month",
+            ParseErrorKind::InvalidDay(_) => "invalid_day",
+            ParseErrorKind::InvalidTime(_) => "invalid_time",
+            ParseErrorKind::MissingHostname => "missing_hostname",
+            ParseErrorKind::MissingProcess => "missing_process",
+            ParseErrorKind::MissingPid(_) => "missing_pid",
+            ParseErrorKind::InvalidPid(_) => "invalid_pid",
+            ParseErrorKind::OrphanedRepeat => "orphaned_repeat",
+            ParseErrorKind::InvalidPriority(_) => "invalid_priority",
+            ParseErrorKind::InvalidVersion(_) => "invalid_version",
+            ParseErrorKind::InvalidTimestamp(_) => "invalid_timestamp",
+            ParseErrorKind::InvalidStructuredData(_) => "invalid_structured_data",
+            ParseErrorKind::PatternMismatch(_) => "pattern_mismatch",
+            ParseErrorKind::MissingTimestamp => "missing_timestamp",
+            ParseErrorKind::InvalidJson(_) => "invalid_json",
+        }
+    }
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::InvalidMonth(token) => write!(f, "invalid month: {:?}", token),
+            ParseErrorKind::InvalidDay(token) => write!(f, "invalid day: {:?}", token),
+            ParseErrorKind::InvalidTime(token) => write!(f, "invalid time: {:?}", token),
+            ParseErrorKind::MissingHostname => f.write_str("no hostname found"),
+            ParseErrorKind::MissingProcess => f.write_str("no process found"),
+            ParseErrorKind::MissingPid(token) => write!(f, "no [pid] after process: {:?}", token),
+            ParseErrorKind::InvalidPid(token) => write!(f, "invalid pid: {:?}", token),
+            ParseErrorKind::OrphanedRepeat => f.write_str("repeat marker with no previous entry"),
+            ParseErrorKind::InvalidPriority(token) => write!(f, "invalid <PRI>: {:?}", token),
+            ParseErrorKind::InvalidVersion(token) => write!(f, "invalid version: {:?}", token),
+            ParseErrorKind::InvalidTimestamp(token) => write!(f, "invalid timestamp: {:?}", token),
+            ParseErrorKind::InvalidStructuredData(token) => {
+                write!(f, "invalid structured data: {:?}", token)
+            }
+            ParseErrorKind::PatternMismatch(expected) => write!(f, "expected {:?}", expected),
+            ParseErrorKind::MissingTimestamp => f.write_str("no timestamp found"),
+            ParseErrorKind::InvalidJson(message) => write!(f, "invalid JSON: {}", message),
+        }
+    }
+}
@@ -0,0 +1,384 @@
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::input::{self, Compression, LineParser, ParsedLine};
+use crate::journal;
+
+/// How many of the last bytes read are kept to notice the file being
+/// rewritten under them.
+const TAIL_BYTES: usize = 64;
+
+/// Keeps a log file open and hands back lines as they are appended, like
+/// `tail -F`.
+///
+/// Truncation restarts from the beginning. It is noticed by the last bytes
+/// read no longer being where they were, so a file truncated and then written
+/// past the old end before the next poll is caught as well as one that shrank. Rename-based rotation, where `system.log` is moved aside
+/// and a new file created in its place, is detected by the path pointing at
+/// a different file: the rest of the old file is drained, then the new one is
+/// read from the start.
+///
+/// The file is read as plain lines, so compressed files, `journalctl -o
+/// export` streams and JSON arrays, which [`input::open`] decodes, cannot be
+/// followed; [`Follower::open`] rejects them.
+pub struct Follower {
+    path: PathBuf,
+    reader: BufReader<File>,
+    identity: Option<FileId>,
+    position: u64,
+    /// The last bytes read, up to [`TAIL_BYTES`], ending at `position`.
+    tail: Vec<u8>,
+    /// Bytes of a line not yet finished, possibly ending mid-character.
+    partial: Vec<u8>,
+    parser: LineParser,
+}
+
+impl Follower {
+    /// Opens `path`, positioned at its end unless `from_start` is set. A file
+    /// that cannot be read line by line is an [`io::ErrorKind::Unsupported`]
+    /// error saying why.
+    pub fn open(path: &Path, from_start: bool) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+        let mut reader = BufReader::new(file);
+        if let Some(reason) = unfollowable(reader.fill_buf()?) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("cannot follow {reason}"),
+            ));
+        }
+        let mut tail = Vec::new();
+        let position = if from_start {
+            0
+        } else {
+            let end = reader.seek(SeekFrom::End(0))?;
+            let start = end.saturating_sub(TAIL_BYTES as u64);
+            reader.seek(SeekFrom::Start(start))?;
+            reader.read_to_end(&mut tail)?;
+            start + tail.len() as u64
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            reader,
+            identity: FileId::of(&metadata),
+            position,
+            tail,
+            partial: Vec::new(),
+            parser: LineParser::new(),
+        })
+    }
+
+    /// Replaces the default [`LineParser`]. Its line numbers count every line
+    /// seen since the follower was opened, across truncations and rotations.
+    pub fn with_parser(mut self, parser: LineParser) -> Self {
+        self.parser = parser;
+        self
+    }
+
+    /// Returns the complete lines appended since the last call. A trailing
+    /// line without a newline is held back until it is finished, even if it
+    /// stops inside a multi-byte character; invalid UTF-8 in a finished line
+    /// is replaced with U+FFFD rather than ending the follow. The last entry
+    /// is held back too, in case continuation lines follow it, until a poll
+    /// finds nothing new, so a crash report written over several polls is
+    /// still joined into one entry.
+    pub fn poll(&mut self) -> io::Result<Vec<ParsedLine<'static>>> {
+        let mut lines = Vec::new();
+        if self.rewritten()? {
+            self.partial.clear();
+            self.tail.clear();
+            self.position = self.reader.seek(SeekFrom::Start(0))?;
+        }
+        let read = self.read_available(&mut lines)? + self.poll_file(&mut lines)?;
+        if read == 0 {
+            lines.extend(self.parser.finish().map(ParsedLine::into_owned));
+        }
+        Ok(lines)
+    }
+
+    /// Whether the bytes last read are no longer where they were read from,
+    /// because the file was truncated, whatever was written after that.
+    fn rewritten(&mut self) -> io::Result<bool> {
+        if self.tail.is_empty() {
+            return Ok(false);
+        }
+        self.reader
+            .seek(SeekFrom::Start(self.position - self.tail.len() as u64))?;
+        let mut bytes = vec![0; self.tail.len()];
+        match self.reader.read_exact(&mut bytes) {
+            Ok(()) => Ok(bytes != self.tail),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(true),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Reopens the file after rotation, returning how many bytes were then
+    /// read.
+    fn poll_file(&mut self, lines: &mut Vec<ParsedLine<'static>>) -> io::Result<u64> {
+        let metadata = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            // Rotated away and not yet recreated; keep the old file open.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(err),
+        };
+        if FileId::of(&metadata) != self.identity {
+            self.flush_partial(lines);
+            let file = File::open(&self.path)?;
+            self.identity = FileId::of(&file.metadata()?);
+            self.reader = BufReader::new(file);
+            self.position = 0;
+            self.tail.clear();
+            self.read_available(lines)
+        } else {
+            Ok(0)
+        }
+    }
+
+    /// Reads to the end of the file, returning how many bytes that was.
+    fn read_available(&mut self, lines: &mut Vec<ParsedLine<'static>>) -> io::Result<u64> {
+        let mut total = 0;
+        loop {
+            let read = self.reader.read_until(b'\n', &mut self.partial)?;
+            if read == 0 {
+                return Ok(total);
+            }
+            total += read as u64;
+            self.position += read as u64;
+            self.tail
+                .extend_from_slice(&self.partial[self.partial.len() - read..]);
+            let excess = self.tail.len().saturating_sub(TAIL_BYTES);
+            self.tail.drain(..excess);
+            if self.partial.ends_with(b"\n") {
+                let text = String::from_utf8_lossy(&self.partial);
+                let text = text.trim_end_matches(['\n', '\r']);
+                lines.extend(self.parser.push(text).map(ParsedLine::into_owned));
+                self.partial.clear();
+            }
+        }
+    }
+
+    fn flush_partial(&mut self, lines: &mut Vec<ParsedLine<'static>>) {
+        if !self.partial.is_empty() {
+            let text = String::from_utf8_lossy(&self.partial);
+            lines.extend(self.parser.push(&text).map(ParsedLine::into_owned));
+            self.partial.clear();
+        }
+    }
+}
+
+/// Why a file starting with `header` cannot be followed, if it cannot.
+fn unfollowable(header: &[u8]) -> Option<&'static str> {
+    match Compression::detect(header) {
+        Compression::Gzip => return Some("a gzip-compressed file"),
+        Compression::Bzip2 => return Some("a bzip2-compressed file"),
+        Compression::Zstd => return Some("a zstd-compressed file"),
+        Compression::None => {}
+    }
+    if journal::is_export(header) {
+        Some("a `journalctl -o export` stream; follow `journalctl -o json` output instead")
+    } else if input::is_json_array(header) {
+        Some("a JSON array; follow `log show --style ndjson` output instead")
+    } else {
+        None
+    }
+}
+
+/// Device and inode, used to notice when a path starts naming another file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileId {
+    device: u64,
+    inode: u64,
+}
+
+impl FileId {
+    #[cfg(unix)]
+    fn of(metadata: &Metadata) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+        Some(Self {
+            device: metadata.dev(),
+            inode: metadata.ino(),
+        })
+    }
+
+    /// Without inodes only truncation can be detected.
+    #[cfg(not(unix))]
+    fn of(_metadata: &Metadata) -> Option<Self> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::Scratch;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    /// A directory holding `system.log` with one line, and that file's path.
+    fn scratch_log(name: &str) -> (Scratch, PathBuf) {
+        let dir = Scratch::dir(&format!("follow-{name}"));
+        let path = dir.join("system.log");
+        fs::write(&path, "Jul  1 09:00:55 host kernel[0]: old\n").unwrap();
+        (dir, path)
+    }
+
+    fn append(path: &Path, text: impl AsRef<[u8]>) {
+        let mut file = OpenOptions::new().append(true).open(path).unwrap();
+        file.write_all(text.as_ref()).unwrap();
+    }
+
+    fn messages(lines: Vec<ParsedLine<'static>>) -> Vec<String> {
+        lines
+            .into_iter()
+            .map(|line| line.entry.unwrap().message.into_owned())
+            .collect()
+    }
+
+    /// Polls until a poll finds nothing new, which hands back the entry held
+    /// for continuation lines.
+    fn settle(follower: &mut Follower) -> Vec<String> {
+        let mut lines = follower.poll().unwrap();
+        lines.extend(follower.poll().unwrap());
+        messages(lines)
+    }
+
+    #[test]
+    fn follow_appended_lines_and_hold_partial_ones() {
+        let (_dir, path) = scratch_log("append");
+        let mut follower = Follower::open(&path, false).unwrap();
+        assert!(follower.poll().unwrap().is_empty());
+
+        append(
+            &path,
+            "Jul  1 09:00:56 host kernel[0]: new\nJul  1 09:00:57 host ker",
+        );
+        assert_eq!(settle(&mut follower), vec!["new"]);
+        append(&path, "nel[0]: finished\n");
+        assert_eq!(settle(&mut follower), vec!["finished"]);
+    }
+
+    #[test]
+    fn follow_holds_a_character_split_across_polls() {
+        let (_dir, path) = scratch_log("utf8");
+        let mut follower = Follower::open(&path, false).unwrap();
+        append(&path, b"Jul  1 09:00:56 host kernel[0]: caf\xc3");
+        assert!(follower.poll().unwrap().is_empty());
+        append(
+            &path,
+            b"\xa9 done\nJul  1 09:00:57 host kernel[0]: bad \xff byte\n",
+        );
+        assert_eq!(
+            settle(&mut follower),
+            vec!["caf\u{e9} done", "bad \u{fffd} byte"]
+        );
+    }
+
+    #[test]
+    fn follow_rejects_input_that_is_not_plain_lines() {
+        let (_dir, path) = scratch_log("unfollowable");
+        for (text, reason) in [
+            (&b"\x1f\x8b\x08\x00"[..], "gzip"),
+            (b"__CURSOR=s=1\nMESSAGE=hi\n\n", "journalctl -o export"),
+            (b"[{\"eventMessage\": \"hi\"}", "JSON array"),
+        ] {
+            fs::write(&path, text).unwrap();
+            let err = Follower::open(&path, false).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+            assert!(err.to_string().contains(reason), "{err}");
+        }
+        fs::write(&path, "{\"eventMessage\": \"hi\"}\n").unwrap();
+        assert!(Follower::open(&path, false).is_ok());
+    }
+
+    #[test]
+    fn follow_joins_continuation_lines_across_polls() {
+        let (_dir, path) = scratch_log("continuation");
+        let mut follower = Follower::open(&path, false).unwrap();
+        append(
+            &path,
+            "Jul  1 09:00:56 host ReportCrash[512]: Thread 0 Crashed:\n",
+        );
+        assert!(follower.poll().unwrap().is_empty());
+        append(&path, "\t0   libsystem_kernel.dylib  0x00007fff\n");
+        assert!(follower.poll().unwrap().is_empty());
+        let lines = follower.poll().unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].continuations, 1);
+        assert_eq!(
+            lines[0].entry.as_ref().unwrap().message,
+            "Thread 0 Crashed:\n\t0   libsystem_kernel.dylib  0x00007fff"
+        );
+    }
+
+    #[test]
+    fn follow_from_start_reads_existing_lines() {
+        let (_dir, path) = scratch_log("start");
+        let mut follower = Follower::open(&path, true).unwrap();
+        assert_eq!(settle(&mut follower), vec!["old"]);
+    }
+
+    #[test]
+    fn follow_restarts_after_truncation() {
+        let (_dir, path) = scratch_log("truncate");
+        let mut follower = Follower::open(&path, false).unwrap();
+        fs::write(&path, "Jul  1 09:01:00 host x[1]: a\n").unwrap();
+        assert_eq!(settle(&mut follower), vec!["a"]);
+    }
+
+    #[test]
+    fn follow_restarts_after_truncation_and_a_longer_rewrite() {
+        let (_dir, path) = scratch_log("rewrite");
+        let mut follower = Follower::open(&path, false).unwrap();
+        fs::write(
+            &path,
+            "Jul  1 09:01:00 host x[1]: a line longer than the old one\n\
+             Jul  1 09:01:01 host x[1]: b\n",
+        )
+        .unwrap();
+        assert_eq!(
+            settle(&mut follower),
+            vec!["a line longer than the old one", "b"]
+        );
+
+        append(&path, "Jul  1 09:01:02 host x[1]: c\n");
+        assert_eq!(settle(&mut follower), vec!["c"]);
+        fs::write(
+            &path,
+            "Jul  1 09:02:00 host x[1]: rewritten with more than before\n\
+             Jul  1 09:02:01 host x[1]: and more again, past the old end\n\
+             Jul  1 09:02:02 host x[1]: d\n",
+        )
+        .unwrap();
+        assert_eq!(
+            settle(&mut follower),
+            vec![
+                "rewritten with more than before",
+                "and more again, past the old end",
+                "d"
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follow_drains_old_file_then_reads_rotated_one() {
+        let (_dir, path) = scratch_log("rotate");
+        let rotated = path.with_extension("log.0");
+        let mut follower = Follower::open(&path, false).unwrap();
+
+        append(
+            &path,
+            "Jul  1 09:00:58 host kernel[0]: last before rotation\n",
+        );
+        fs::rename(&path, &rotated).unwrap();
+        assert_eq!(settle(&mut follower), vec!["last before rotation"]);
+
+        fs::write(
+            &path,
+            "Jul  1 09:00:59 host kernel[0]: first after rotation\n",
+        )
+        .unwrap();
+        assert_eq!(settle(&mut follower), vec!["first after rotation"]);
+    }
+}
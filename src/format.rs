@@ -0,0 +1,259 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::entry::{LogEntry, parse_log_entry, parse_repeat_marker, starts_with_month};
+use crate::error::ParseError;
+use crate::journal::parse_journal;
+use crate::pattern::Pattern;
+use crate::priority;
+use crate::rfc5424::parse_rfc5424;
+use crate::unified::parse_unified;
+
+/// Line layouts the analyzer can read. Every format produces [`LogEntry`]
+/// values, so the rest of the pipeline does not care which one was used.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// BSD/macOS syslog, `Jul  1 09:00:55 host process[pid]: message`.
+    #[default]
+    Bsd,
+    /// RFC 5424, `<PRI>1 2024-07-01T09:00:55Z host app procid msgid [sd] msg`.
+    Rfc5424,
+    /// Apple unified log events, one JSON object per line as written by
+    /// `log show --style ndjson`.
+    Unified,
+    /// systemd journal entries, one JSON object per line as written by
+    /// `journalctl -o json`.
+    Journal,
+    /// A user-defined [`Pattern`].
+    Pattern(Arc<Pattern>),
+}
+
+impl LogFormat {
+    pub const BUILT_IN: [LogFormat; 4] = [
+        LogFormat::Bsd,
+        LogFormat::Rfc5424,
+        LogFormat::Unified,
+        LogFormat::Journal,
+    ];
+
+    pub fn name(&self) -> &str {
+        match self {
+            LogFormat::Bsd => "bsd",
+            LogFormat::Rfc5424 => "rfc5424",
+            LogFormat::Unified => "unified",
+            LogFormat::Journal => "journal",
+            LogFormat::Pattern(pattern) => pattern.name(),
+        }
+    }
+
+    pub fn parse<'a>(&self, line: &'a str) -> Result<LogEntry<'a>, ParseError> {
+        match self {
+            LogFormat::Bsd => parse_log_entry(line),
+            LogFormat::Rfc5424 => parse_rfc5424(line),
+            LogFormat::Unified => parse_unified(line),
+            LogFormat::Journal => parse_journal(line),
+            LogFormat::Pattern(pattern) => pattern.parse(line),
+        }
+    }
+
+    /// Whether `line` begins a new entry rather than continuing the previous
+    /// one: it is not indented and opens the way this format's lines do.
+    pub fn starts_entry(&self, line: &str) -> bool {
+        if line.starts_with(char::is_whitespace) {
+            return false;
+        }
+        match self {
+            LogFormat::Bsd => starts_with_month(line),
+            LogFormat::Rfc5424 => matches!(priority::strip_priority(line), Some(Ok(_))),
+            LogFormat::Unified | LogFormat::Journal => line.starts_with('{'),
+            LogFormat::Pattern(pattern) => pattern.starts_entry(line),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    /// Looks up a built-in format by name.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        LogFormat::BUILT_IN
+            .into_iter()
+            .find(|format| format.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("unknown log format: {}", name))
+    }
+}
+
+/// The best-scoring format for a sample of lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Detection {
+    pub format: LogFormat,
+    /// Sampled lines the format parsed, or that continue an entry it parsed.
+    pub matched: usize,
+    /// Lines considered; blank lines and repeat markers are left out.
+    pub sampled: usize,
+}
+
+impl Detection {
+    /// Fraction of the sample that parsed, from 0 to 1.
+    pub fn score(&self) -> f64 {
+        self.matched as f64 / self.sampled as f64
+    }
+}
+
+/// Tries every one of `formats` on `lines` and returns the one that parses
+/// the most; ties go to the earlier format. `None` when there is nothing to
+/// judge by.
+pub fn detect<'a>(
+    formats: &[LogFormat],
+    lines: impl IntoIterator<Item = &'a str>,
+) -> Option<Detection> {
+    let sample = lines
+        .into_iter()
+        .filter(|line| !line.trim().is_empty() && parse_repeat_marker(line).is_none())
+        .collect::<Vec<_>>();
+    if sample.is_empty() {
+        return None;
+    }
+    formats
+        .iter()
+        .map(|format| Detection {
+            format: format.clone(),
+            matched: matched_lines(format, &sample),
+            sampled: sample.len(),
+        })
+        .reduce(|best, next| {
+            if next.matched > best.matched {
+                next
+            } else {
+                best
+            }
+        })
+}
+
+/// Counts the lines `format` parses, along with the continuation lines that
+/// would be joined onto those entries.
+fn matched_lines(format: &LogFormat, sample: &[&str]) -> usize {
+    let mut in_entry = false;
+    sample
+        .iter()
+        .filter(|line| {
+            if !in_entry || format.starts_entry(line) {
+                in_entry = format.parse(line).is_ok();
+            }
+            in_entry
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_round_trip_their_names() {
+        for format in LogFormat::BUILT_IN {
+            assert_eq!(format.name().parse::<LogFormat>(), Ok(format.clone()));
+        }
+        assert!("json".parse::<LogFormat>().is_err());
+    }
+
+    #[test]
+    fn detect_picks_the_format_that_parses_most_lines() {
+        let mac_2k = include_str!("Mac_2k.log");
+        let detection = detect(&LogFormat::BUILT_IN, mac_2k.lines().take(100)).unwrap();
+        assert_eq!(detection.format, LogFormat::Bsd);
+        assert_eq!(detection.sampled, 100);
+        assert_eq!(detection.score(), 1.0);
+
+        let lines = [
+            "garbage",
+            "<14>1 2024-07-01T09:00:55Z host app 1 - - one",
+            "",
+            "<14>1 2024-07-01T09:00:56Z host app 1 - - two",
+        ];
+        let detection = detect(&LogFormat::BUILT_IN, lines).unwrap();
+        assert_eq!(detection.format, LogFormat::Rfc5424);
+        assert_eq!((detection.matched, detection.sampled), (2, 3));
+
+        assert_eq!(
+            detect(
+                &LogFormat::BUILT_IN,
+                ["", "--- last message repeated 2 times ---"]
+            ),
+            None
+        );
+        assert_eq!(
+            detect(&LogFormat::BUILT_IN, ["garbage"]).unwrap().matched,
+            0
+        );
+    }
+
+    #[test]
+    fn detect_counts_continuation_lines_of_parsed_entries() {
+        let lines = [
+            "Jul  1 09:00:55 host ReportCrash[512]: Thread 0 Crashed:",
+            "\t0   libsystem_kernel.dylib  0x00007fff",
+            "Binary Images:",
+            "garbage",
+        ];
+        let detection = detect(&LogFormat::BUILT_IN, lines).unwrap();
+        assert_eq!(detection.format, LogFormat::Bsd);
+        assert_eq!(detection.matched, 4);
+
+        let lines = ["garbage", "  indented"];
+        assert_eq!(detect(&LogFormat::BUILT_IN, lines).unwrap().matched, 0);
+    }
+
+    #[test]
+    fn detect_considers_user_patterns() {
+        let worker = Pattern::new(
+            "worker",
+            "%{DATE} %{TIME} [%{_LEVEL}] %{PROC} pid=%{PID} %{MSG}",
+        )
+        .unwrap();
+        let mut formats = LogFormat::BUILT_IN.to_vec();
+        formats.push(LogFormat::Pattern(Arc::new(worker)));
+        let detection = detect(
+            &formats,
+            ["2024-07-01 09:00:55.123 [INFO] worker-3 pid=42 msg"],
+        )
+        .unwrap();
+        assert_eq!(detection.format.name(), "worker");
+        assert_eq!(detection.matched, 1);
+    }
+
+    #[test]
+    fn indented_lines_never_start_entries() {
+        for format in LogFormat::BUILT_IN {
+            assert!(!format.starts_entry("  Jul  1 09:00:55 host kernel[0]: x"));
+            assert!(!format.starts_entry(""));
+        }
+        assert!(LogFormat::Rfc5424.starts_entry("<14>1 garbage"));
+        assert!(!LogFormat::Rfc5424.starts_entry("Jul  1 09:00:55 host kernel[0]: x"));
+    }
+
+    #[test]
+    fn each_format_reads_its_own_layout() {
+        let bsd = "Jul  1 09:00:55 calvisitor-10-105-160-95 kernel[0]: AppleThunderboltNHIType2";
+        let rfc5424 = "<14>1 2017-07-01T09:00:55-07:00 calvisitor-10-105-160-95 kernel 0 - - hello";
+        assert_eq!(LogFormat::Bsd.parse(bsd).unwrap().process, "kernel");
+        assert!(LogFormat::Bsd.parse(rfc5424).is_err());
+        assert_eq!(LogFormat::Rfc5424.parse(rfc5424).unwrap().process, "kernel");
+        assert!(LogFormat::Rfc5424.parse(bsd).is_err());
+        let unified = r#"{"timestamp":"2017-07-01 09:00:55.000000-0700","processImagePath":"/kernel","eventMessage":"hello"}"#;
+        assert_eq!(LogFormat::Unified.parse(unified).unwrap().process, "kernel");
+        assert!(LogFormat::Unified.parse(bsd).is_err());
+        assert!(LogFormat::Bsd.parse(unified).is_err());
+        let journal = r#"{"__REALTIME_TIMESTAMP":"1498924855000000","_HOSTNAME":"host","_COMM":"kernel","MESSAGE":"hello"}"#;
+        assert_eq!(LogFormat::Journal.parse(journal).unwrap().process, "kernel");
+        assert!(LogFormat::Journal.parse(unified).is_err());
+        assert!(LogFormat::Unified.parse(journal).is_err());
+    }
+}
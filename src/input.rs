@@ -0,0 +1,1096 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use bzip2::read::MultiBzDecoder;
+use chrono::{DateTime, Local, NaiveDate};
+use flate2::bufread::MultiGzDecoder;
+
+use crate::entry::{LogEntry, parse_repeat_marker, repeat_marker_time};
+use crate::error::{ParseError, ParseErrorKind};
+use crate::format::LogFormat;
+use crate::journal::{self, ExportReader};
+use crate::timestamp::{YearResolver, Zone, ZoneConverter};
+
+/// Compression formats recognised by their leading magic bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Bzip2,
+    Zstd,
+}
+
+impl Compression {
+    pub fn detect(header: &[u8]) -> Self {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if header.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// The path that stands for standard input.
+pub const STDIN: &str = "-";
+
+pub fn is_stdin(path: &Path) -> bool {
+    path == Path::new(STDIN)
+}
+
+/// Opens a log file for line-by-line reading, transparently decompressing
+/// gzip, bzip2 and zstd files. The format comes from the file's magic bytes,
+/// not its name, so `system.log.0.gz` and a renamed copy read the same.
+/// [`STDIN`] reads standard input, so `zcat ... | log_analyzer` works too.
+/// A JSON array is read one element per line (see [`split_json_array`]), and
+/// a `journalctl -o export` stream one record per line.
+pub fn open(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let mut reader = if is_stdin(path) {
+        decompress(BufReader::new(io::stdin()))?
+    } else {
+        decompress(BufReader::new(File::open(path)?))?
+    };
+    if journal::is_export(reader.fill_buf()?) {
+        return Ok(Box::new(BufReader::new(ExportReader::new(reader))));
+    }
+    split_json_array(reader)
+}
+
+/// Wraps `reader` in the decoder its first bytes call for.
+pub fn decompress<R: BufRead + 'static>(mut reader: R) -> io::Result<Box<dyn BufRead>> {
+    Ok(match Compression::detect(reader.fill_buf()?) {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Compression::Bzip2 => Box::new(BufReader::new(MultiBzDecoder::new(reader))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+    })
+}
+
+/// Turns input that starts as a JSON array of objects, such as
+/// `log show --style json` output, into one compact object per line, as
+/// `--style ndjson` would have written it. Other input is returned as is.
+/// The array is streamed, not read into memory first.
+pub fn split_json_array(mut reader: Box<dyn BufRead>) -> io::Result<Box<dyn BufRead>> {
+    Ok(if is_json_array(reader.fill_buf()?) {
+        Box::new(BufReader::new(JsonArrayLines {
+            inner: reader,
+            tokens: ArrayTokens::default(),
+            error: None,
+        }))
+    } else {
+        reader
+    })
+}
+
+/// Whether `header` starts a JSON array of objects.
+pub(crate) fn is_json_array(header: &[u8]) -> bool {
+    header
+        .trim_ascii_start()
+        .strip_prefix(b"[")
+        .is_some_and(|rest| rest.trim_ascii_start().starts_with(b"{"))
+}
+
+struct JsonArrayLines {
+    inner: Box<dyn BufRead>,
+    tokens: ArrayTokens,
+    /// A malformed byte found after some lines were already written, reported
+    /// by the next read so those lines are not lost.
+    error: Option<io::Error>,
+}
+
+/// Copies the elements of a JSON array without the whitespace between
+/// tokens, writing a newline where a `,` or the closing `]` ends an element.
+/// Raw newlines can only occur between tokens, as JSON strings escape them.
+/// Unbalanced brackets and anything but whitespace after the closing `]` are
+/// errors.
+#[derive(Default)]
+struct ArrayTokens {
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    in_element: bool,
+    done: bool,
+}
+
+impl ArrayTokens {
+    /// The byte to write for `byte` of the array, if any.
+    fn translate(&mut self, byte: u8) -> Result<Option<u8>, &'static str> {
+        if self.done {
+            return if byte.is_ascii_whitespace() {
+                Ok(None)
+            } else {
+                Err("unexpected data after the closing `]` of the JSON array")
+            };
+        }
+        if self.in_string {
+            match byte {
+                _ if self.escaped => self.escaped = false,
+                b'\\' => self.escaped = true,
+                b'"' => self.in_string = false,
+                _ => {}
+            }
+            return Ok(Some(byte));
+        }
+        match byte {
+            _ if byte.is_ascii_whitespace() => return Ok(None),
+            b'[' if self.depth == 0 => {
+                self.depth = 1;
+                return Ok(None);
+            }
+            b',' | b']' if self.depth == 1 => {
+                self.done = byte == b']';
+                return Ok(std::mem::take(&mut self.in_element).then_some(b'\n'));
+            }
+            b'"' => self.in_string = true,
+            b'[' | b'{' => self.depth += 1,
+            // Depth 1 is the array itself, which only `]` closes, above.
+            b']' | b'}' if self.depth > 1 => self.depth -= 1,
+            b']' | b'}' => return Err("unbalanced brackets in the JSON array"),
+            _ => {}
+        }
+        self.in_element = true;
+        Ok(Some(byte))
+    }
+}
+
+impl Read for JsonArrayLines {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        let mut written = 0;
+        while written < out.len() {
+            let buffer = self.inner.fill_buf()?;
+            if buffer.is_empty() {
+                break;
+            }
+            let mut consumed = 0;
+            let mut malformed = None;
+            for &byte in buffer {
+                if written == out.len() {
+                    break;
+                }
+                consumed += 1;
+                match self.tokens.translate(byte) {
+                    Ok(Some(byte)) => {
+                        out[written] = byte;
+                        written += 1;
+                    }
+                    Ok(None) => {}
+                    Err(message) => {
+                        malformed = Some(io::Error::new(io::ErrorKind::InvalidData, message));
+                        break;
+                    }
+                }
+            }
+            self.inner.consume(consumed);
+            if let Some(err) = malformed {
+                if written == 0 {
+                    return Err(err);
+                }
+                self.error = Some(err);
+                break;
+            }
+        }
+        Ok(written)
+    }
+}
+
+/// Reads up to `count` lines from the start of `reader` and returns them
+/// along with a reader that yields the whole input again, sampled lines
+/// included, so the sample can be inspected before parsing begins.
+pub fn sample_lines(
+    mut reader: Box<dyn BufRead>,
+    count: usize,
+) -> io::Result<(Vec<String>, Box<dyn BufRead>)> {
+    let mut buffer = Vec::new();
+    let mut lines = Vec::new();
+    while lines.len() < count {
+        let start = buffer.len();
+        if reader.read_until(b'\n', &mut buffer)? == 0 {
+            break;
+        }
+        let line = String::from_utf8_lossy(&buffer[start..]);
+        lines.push(line.trim_end_matches(['\n', '\r']).to_string());
+    }
+    let replay = io::Read::chain(io::Cursor::new(buffer), reader);
+    Ok((lines, Box::new(replay)))
+}
+
+/// The local date `path` was last modified, used to infer the year of its
+/// entries. `None` for stdin or when the platform cannot tell.
+pub fn modified_date(path: &Path) -> Option<NaiveDate> {
+    if is_stdin(path) {
+        return None;
+    }
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(DateTime::<Local>::from(modified).date_naive())
+}
+
+/// Expands one command-line argument into the files to read, sorted by path
+/// with numbers in names compared by value, so rotated logs come in order:
+/// `system.log.2` before `system.log.10`.
+/// A glob that matches no files, or a directory that holds none, is a
+/// [`io::ErrorKind::NotFound`] error.
+///
+/// An argument containing `*`, `?` or `[` that does not name an existing file
+/// is treated as a shell-style glob, so `'/var/log/system.log*'` works even
+/// when the shell did not expand it. Directories are walked recursively;
+/// symlinks to files inside them are read, symlinks to directories are not,
+/// so a link back up the tree cannot loop.
+pub fn expand_path(arg: &Path) -> io::Result<Vec<PathBuf>> {
+    if is_stdin(arg) {
+        return Ok(vec![arg.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    let pattern = arg.to_string_lossy();
+    if !arg.exists() && pattern.contains(['*', '?', '[']) {
+        let paths =
+            glob::glob(&pattern).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        for path in paths {
+            collect_files(path.map_err(io::Error::from)?, &mut files)?;
+        }
+        if files.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no files match"));
+        }
+    } else {
+        collect_files(arg.to_path_buf(), &mut files)?;
+        if files.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no files in directory",
+            ));
+        }
+    }
+    files.sort_by(|a, b| compare_paths(a, b).then_with(|| a.cmp(b)));
+    Ok(files)
+}
+
+/// Compares paths component by component, runs of digits by their value.
+fn compare_paths(a: &Path, b: &Path) -> Ordering {
+    let mut a = a.components();
+    let mut b = b.components();
+    loop {
+        let ordering = match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a), Some(b)) => compare_names(
+                &a.as_os_str().to_string_lossy(),
+                &b.as_os_str().to_string_lossy(),
+            ),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+fn compare_names(mut a: &str, mut b: &str) -> Ordering {
+    loop {
+        let a_digits = a.len() - a.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let b_digits = b.len() - b.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let ordering = if a_digits > 0 && b_digits > 0 {
+            let (a_number, b_number) = (
+                a[..a_digits].trim_start_matches('0'),
+                b[..b_digits].trim_start_matches('0'),
+            );
+            let ordering = a_number
+                .len()
+                .cmp(&b_number.len())
+                .then_with(|| a_number.cmp(b_number));
+            (a, b) = (&a[a_digits..], &b[b_digits..]);
+            ordering
+        } else {
+            let mut a_chars = a.chars();
+            let mut b_chars = b.chars();
+            let (a_char, b_char) = (a_chars.next(), b_chars.next());
+            if a_char.is_none() && b_char.is_none() {
+                return Ordering::Equal;
+            }
+            (a, b) = (a_chars.as_str(), b_chars.as_str());
+            a_char.cmp(&b_char)
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+fn collect_files(path: PathBuf, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if path.is_dir() {
+        collect_dir(&path, files)
+    } else {
+        files.push(path);
+        Ok(())
+    }
+}
+
+fn collect_dir(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for dir_entry in fs::read_dir(dir)? {
+        let dir_entry = dir_entry?;
+        let path = dir_entry.path();
+        // `file_type` does not follow symlinks, `is_dir` does.
+        if dir_entry.file_type()?.is_dir() {
+            collect_dir(&path, files)?;
+        } else if !path.is_dir() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Reads the next line into `buffer`, reusing its allocation, and returns it
+/// without its `\n` or `\r\n`; `None` at the end of the input. Invalid UTF-8
+/// is replaced with U+FFFD rather than failing the read, and a valid line is
+/// borrowed from the buffer, not copied.
+pub fn read_line<'b>(
+    reader: &mut impl BufRead,
+    buffer: &'b mut Vec<u8>,
+) -> io::Result<Option<Cow<'b, str>>> {
+    buffer.clear();
+    if reader.read_until(b'\n', buffer)? == 0 {
+        return Ok(None);
+    }
+    if buffer.ends_with(b"\n") {
+        buffer.pop();
+        if buffer.ends_with(b"\r") {
+            buffer.pop();
+        }
+    }
+    Ok(Some(String::from_utf8_lossy(buffer)))
+}
+
+/// A raw line together with the result of parsing it. Both borrow from the
+/// [`LineParser`] that produced them; [`ParsedLine::into_owned`] keeps them
+/// past the next line.
+#[derive(Debug)]
+pub struct ParsedLine<'a> {
+    /// 1-based line number within the source.
+    pub line_number: usize,
+    pub text: Cow<'a, str>,
+    pub entry: Result<LogEntry<'a>, ParseError>,
+    /// How many times the entry occurred: 1 for an ordinary line, N for a
+    /// `--- last message repeated N times ---` marker, which carries the
+    /// text and entry of the line it repeats.
+    pub occurrences: usize,
+    /// Lines after the first that were joined onto this entry; `text` holds
+    /// them all, separated by newlines.
+    pub continuations: usize,
+}
+
+impl ParsedLine<'_> {
+    pub fn into_owned(self) -> ParsedLine<'static> {
+        ParsedLine {
+            line_number: self.line_number,
+            text: Cow::Owned(self.text.into_owned()),
+            entry: self.entry.map(LogEntry::into_owned),
+            occurrences: self.occurrences,
+            continuations: self.continuations,
+        }
+    }
+}
+
+/// Numbers and parses the lines of one source, carrying the state needed to
+/// expand syslog's repeat markers and join multi-line entries.
+///
+/// Lines are fed in with [`LineParser::push`]. An entry is handed back once
+/// the next entry starts, since until then more continuation lines (indented,
+/// or not starting with a timestamp) may follow and become part of its
+/// message; [`LineParser::finish`] hands back the last one. The text of each
+/// entry is kept in buffers that are reused from line to line, and the
+/// entries handed back borrow from them, so reading a file does not allocate
+/// per line.
+#[derive(Debug)]
+pub struct LineParser {
+    line_number: usize,
+    expand_repeats: bool,
+    join_continuations: bool,
+    /// The entry still open to continuation lines, and its text.
+    pending: Option<Pending>,
+    pending_text: String,
+    /// Text of the entry handed back last.
+    completed_text: String,
+    /// Text of the last entry that parsed, for the repeat markers after it;
+    /// empty before the first, as an empty line never parses.
+    previous_text: String,
+    years: YearResolver,
+    zones: ZoneConverter,
+    format: LogFormat,
+}
+
+#[derive(Debug)]
+struct Pending {
+    line_number: usize,
+    continuations: usize,
+    /// N for a repeat marker, which takes no continuation lines.
+    repeat: Option<u32>,
+    /// Whether the first line parses, worked out once a continuation line
+    /// needs to know.
+    parses: Option<bool>,
+}
+
+impl Default for LineParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LineParser {
+    pub fn new() -> Self {
+        Self {
+            line_number: 0,
+            expand_repeats: true,
+            join_continuations: true,
+            pending: None,
+            pending_text: String::new(),
+            completed_text: String::new(),
+            previous_text: String::new(),
+            years: YearResolver::ending_today(),
+            zones: ZoneConverter::new(Zone::Local),
+            format: LogFormat::Bsd,
+        }
+    }
+
+    /// Layout of the lines; BSD syslog by default.
+    pub fn with_format(mut self, format: LogFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Where entry timestamps get their year from; by default the log is
+    /// assumed to end today.
+    pub fn with_years(mut self, years: YearResolver) -> Self {
+        self.years = years;
+        self
+    }
+
+    /// Zones used to convert timestamps to UTC; by default this machine's.
+    pub fn with_zones(mut self, zones: ZoneConverter) -> Self {
+        self.zones = zones;
+        self
+    }
+
+    /// When disabled, repeat markers are parsed like any other line (and
+    /// rejected) instead of being credited to the previous entry.
+    pub fn expand_repeats(mut self, expand_repeats: bool) -> Self {
+        self.expand_repeats = expand_repeats;
+        self
+    }
+
+    /// When disabled, every line is an entry of its own, and continuation
+    /// lines are rejected like any other line that does not parse.
+    pub fn join_continuations(mut self, join_continuations: bool) -> Self {
+        self.join_continuations = join_continuations;
+        self
+    }
+
+    /// Feeds in the next line and returns the entry it completes, if any.
+    pub fn push(&mut self, text: &str) -> Option<ParsedLine<'_>> {
+        self.line_number += 1;
+        let repeat = if self.expand_repeats {
+            parse_repeat_marker(text)
+        } else {
+            None
+        };
+        if self.join_continuations && repeat.is_none() && self.continues_pending(text) {
+            self.pending_text.push('\n');
+            self.pending_text.push_str(text);
+            if let Some(pending) = &mut self.pending {
+                pending.continuations += 1;
+            }
+            return None;
+        }
+        let started = Pending {
+            line_number: self.line_number,
+            continuations: 0,
+            repeat,
+            parses: None,
+        };
+        let completed = self.pending.replace(started);
+        std::mem::swap(&mut self.pending_text, &mut self.completed_text);
+        self.pending_text.clear();
+        self.pending_text.push_str(text);
+        if !self.join_continuations {
+            return self.finish();
+        }
+        completed.map(|pending| self.complete(pending))
+    }
+
+    /// Returns the entry still waiting for continuation lines, at the end of
+    /// the input or when no more lines are expected for now.
+    pub fn finish(&mut self) -> Option<ParsedLine<'_>> {
+        let pending = self.pending.take()?;
+        std::mem::swap(&mut self.pending_text, &mut self.completed_text);
+        Some(self.complete(pending))
+    }
+
+    /// Whether `text` belongs to the pending entry: it does not start an
+    /// entry of its own, and the pending one parsed.
+    fn continues_pending(&mut self, text: &str) -> bool {
+        let Some(pending) = &mut self.pending else {
+            return false;
+        };
+        if pending.repeat.is_some() || self.format.starts_entry(text) {
+            return false;
+        }
+        *pending
+            .parses
+            .get_or_insert_with(|| self.format.parse(&self.pending_text).is_ok())
+    }
+
+    /// Parses the entry whose text was just moved to `completed_text`.
+    fn complete(&mut self, pending: Pending) -> ParsedLine<'_> {
+        let Pending {
+            line_number,
+            continuations,
+            repeat,
+            ..
+        } = pending;
+        if let Some(count) = repeat {
+            if self.previous_text.is_empty() {
+                return ParsedLine {
+                    line_number,
+                    text: Cow::Borrowed(&self.completed_text),
+                    entry: Err(ParseError::new(ParseErrorKind::OrphanedRepeat, 0)
+                        .with_line_number(line_number)),
+                    occurrences: 1,
+                    continuations,
+                };
+            }
+            // The repeats happened when the marker was logged, not when the
+            // entry they repeat was.
+            let entry = self
+                .format
+                .parse(&self.previous_text)
+                .map(|mut entry| {
+                    if let Some((month, day, time)) = repeat_marker_time(&self.completed_text) {
+                        entry.month = month;
+                        entry.day = day;
+                        entry.time = time;
+                        entry.timestamp = None;
+                        entry.timestamp_utc = None;
+                    }
+                    fill_timestamps(&mut self.years, &mut self.zones, entry)
+                })
+                .map_err(|err| err.with_line_number(line_number));
+            return ParsedLine {
+                line_number,
+                text: Cow::Borrowed(&self.previous_text),
+                entry,
+                occurrences: count as usize,
+                continuations,
+            };
+        }
+        let entry = parse_entry(
+            &self.format,
+            &mut self.years,
+            &mut self.zones,
+            &self.completed_text,
+            line_number,
+        );
+        if entry.is_ok() {
+            self.previous_text.clone_from(&self.completed_text);
+        } else {
+            // A marker after a rejected line repeats that line, not the
+            // last one that parsed.
+            self.previous_text.clear();
+        }
+        ParsedLine {
+            line_number,
+            text: Cow::Borrowed(&self.completed_text),
+            entry,
+            occurrences: 1,
+            continuations,
+        }
+    }
+}
+
+/// Parses the text of one entry and fills in the timestamps its format
+/// leaves out.
+fn parse_entry<'t>(
+    format: &LogFormat,
+    years: &mut YearResolver,
+    zones: &mut ZoneConverter,
+    text: &'t str,
+    line_number: usize,
+) -> Result<LogEntry<'t>, ParseError> {
+    format
+        .parse(text)
+        .map(|entry| fill_timestamps(years, zones, entry))
+        .map_err(|err| err.with_line_number(line_number))
+}
+
+/// Fills in the timestamps a parsed entry's format leaves out.
+fn fill_timestamps<'t>(
+    years: &mut YearResolver,
+    zones: &mut ZoneConverter,
+    mut entry: LogEntry<'t>,
+) -> LogEntry<'t> {
+    // The journal records instants, shown in the host's zone.
+    if let (None, Some(utc)) = (entry.timestamp, entry.timestamp_utc) {
+        entry.set_local_time(zones.to_local(&entry.hostname, utc));
+    }
+    // Formats with a full timestamp (RFC 5424) fill these in.
+    if entry.timestamp.is_none() {
+        entry.timestamp = years.resolve(&entry);
+    }
+    if entry.timestamp_utc.is_none() {
+        entry.timestamp_utc = entry
+            .timestamp
+            .and_then(|local| zones.to_utc(&entry.hostname, local));
+    }
+    entry
+}
+
+/// Iterator over the lines of a reader, parsing each one as it is read.
+/// Its lines are owned; reading with [`read_line`] and [`LineParser::push`]
+/// avoids copying them.
+pub struct ParsedLines<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    parser: LineParser,
+}
+
+pub fn parse_lines<R: BufRead>(reader: R) -> ParsedLines<R> {
+    parse_lines_with(reader, LineParser::new())
+}
+
+pub fn parse_lines_with<R: BufRead>(reader: R, parser: LineParser) -> ParsedLines<R> {
+    ParsedLines {
+        reader,
+        buffer: Vec::new(),
+        parser,
+    }
+}
+
+impl<R: BufRead> Iterator for ParsedLines<R> {
+    type Item = io::Result<ParsedLine<'static>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match read_line(&mut self.reader, &mut self.buffer) {
+                Ok(Some(text)) => {
+                    if let Some(line) = self.parser.push(&text) {
+                        return Some(Ok(line.into_owned()));
+                    }
+                }
+                Ok(None) => return self.parser.finish().map(|line| Ok(line.into_owned())),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::Scratch;
+    use std::io::Write;
+
+    fn scratch_dir(name: &str) -> Scratch {
+        let dir = Scratch::dir(name);
+        fs::create_dir_all(dir.join("archive")).unwrap();
+        for file in [
+            "system.log",
+            "system.log.1",
+            "system.log.0",
+            "archive/system.log.2",
+        ] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn expand_globs_and_directories() {
+        let dir = scratch_dir("expand");
+        let files = expand_path(&dir.join("system.log*")).unwrap();
+        assert_eq!(
+            files,
+            vec![
+                dir.join("system.log"),
+                dir.join("system.log.0"),
+                dir.join("system.log.1"),
+            ]
+        );
+
+        let files = expand_path(&dir).unwrap();
+        assert_eq!(files.len(), 4);
+        assert_eq!(files[0], dir.join("archive/system.log.2"));
+
+        assert_eq!(
+            expand_path(Path::new("-")).unwrap(),
+            vec![PathBuf::from("-")]
+        );
+
+        let err = expand_path(&dir.join("*.gz")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        fs::create_dir_all(dir.join("empty/nested")).unwrap();
+        let err = expand_path(&dir.join("empty")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(expand_path(&dir).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn rotated_logs_sort_by_number() {
+        let mut paths = [
+            "system.log.10.gz",
+            "system.log.2.gz",
+            "system.log",
+            "archive/system.log.1",
+            "system.log.02.gz",
+            "system.log.0.gz",
+            "archive2",
+        ]
+        .map(PathBuf::from);
+        paths.sort_by(|a, b| compare_paths(a, b).then_with(|| a.cmp(b)));
+        assert_eq!(
+            paths.map(|path| path.to_string_lossy().into_owned()),
+            [
+                "archive/system.log.1",
+                "archive2",
+                "system.log",
+                "system.log.0.gz",
+                "system.log.02.gz",
+                "system.log.2.gz",
+                "system.log.10.gz",
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_directories_are_not_walked() {
+        let dir = scratch_dir("symlinks");
+        std::os::unix::fs::symlink(&dir, dir.join("archive/loop")).unwrap();
+        std::os::unix::fs::symlink(dir.join("system.log"), dir.join("archive/current.log"))
+            .unwrap();
+        let files = expand_path(&dir).unwrap();
+        assert_eq!(files.len(), 5);
+        assert_eq!(files[0], dir.join("archive/current.log"));
+        assert_eq!(expand_path(&dir.join("archive/loop")).unwrap().len(), 5);
+    }
+
+    const SAMPLE: &str =
+        "Jul  1 09:00:55 host kernel[0]: first\nJul  1 09:00:56 host kernel[0]: second\n";
+
+    fn read_all(reader: Box<dyn BufRead>) -> Vec<String> {
+        parse_lines(reader)
+            .map(|line| line.unwrap().entry.unwrap().message.into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn detect_compression_from_magic_bytes() {
+        assert_eq!(Compression::detect(b"\x1f\x8b\x08\x00"), Compression::Gzip);
+        assert_eq!(Compression::detect(b"BZh91AY"), Compression::Bzip2);
+        assert_eq!(Compression::detect(b"\x28\xb5\x2f\xfd"), Compression::Zstd);
+        assert_eq!(Compression::detect(b"Jul  1"), Compression::None);
+        assert_eq!(Compression::detect(b""), Compression::None);
+    }
+
+    #[test]
+    fn decompress_gzip_bzip2_and_zstd() {
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(SAMPLE.as_bytes()).unwrap();
+        let gzip = gzip.finish().unwrap();
+
+        let mut bzip2 = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        bzip2.write_all(SAMPLE.as_bytes()).unwrap();
+        let bzip2 = bzip2.finish().unwrap();
+
+        let zstd = zstd::encode_all(SAMPLE.as_bytes(), 0).unwrap();
+
+        for compressed in [gzip, bzip2, zstd, SAMPLE.as_bytes().to_vec()] {
+            let reader = decompress(io::Cursor::new(compressed)).unwrap();
+            assert_eq!(read_all(reader), vec!["first", "second"]);
+        }
+    }
+
+    #[test]
+    fn repeat_markers_carry_the_previous_entry() {
+        let input = "--- last message repeated 1 time ---\n\
+                     Jul  1 09:00:55 host kernel[0]: first\n\
+                     Jul  1 09:00:58 --- last message repeated 4 times ---\n";
+        let lines = parse_lines(input.as_bytes())
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            lines[0].entry.as_ref().unwrap_err().kind,
+            ParseErrorKind::OrphanedRepeat
+        );
+        assert_eq!(lines[2].line_number, 3);
+        assert_eq!(lines[2].occurrences, 4);
+        assert_eq!(lines[2].text, "Jul  1 09:00:55 host kernel[0]: first");
+        assert_eq!(lines[2].entry.as_ref().unwrap().message, "first");
+
+        let parser = LineParser::new().expand_repeats(false);
+        let lines = parse_lines_with(input.as_bytes(), parser)
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert!(lines[2].entry.is_err());
+        assert_eq!(lines[2].occurrences, 1);
+    }
+
+    #[test]
+    fn repeats_are_timed_by_their_marker() {
+        let input = "Jul  1 09:00:55 host kernel[0]: first\n\
+                     Jul  1 09:00:58 --- last message repeated 4 times ---\n\
+                     --- last message repeated 2 times ---\n";
+        let parser = LineParser::new().with_years(YearResolver::starting_in(2017));
+        let timestamps = parse_lines_with(input.as_bytes(), parser)
+            .map(|line| line.unwrap().entry.unwrap().timestamp.unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            timestamps,
+            vec![
+                "2017-07-01 09:00:55",
+                "2017-07-01 09:00:58",
+                "2017-07-01 09:00:55"
+            ]
+        );
+    }
+
+    #[test]
+    fn repeats_of_a_rejected_line_are_orphaned() {
+        let input = "Jul  1 09:00:55 host kernel[0]: first\n\
+                     Jul  1 09:00:56 garbled\n\
+                     Jul  1 09:00:58 --- last message repeated 4 times ---\n";
+        let lines = parse_lines(input.as_bytes())
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert!(lines[1].entry.is_err());
+        assert_eq!(
+            lines[2].entry.as_ref().unwrap_err().kind,
+            ParseErrorKind::OrphanedRepeat
+        );
+        assert_eq!(lines[2].occurrences, 1);
+    }
+
+    #[test]
+    fn parsed_entries_get_timestamps_across_new_year() {
+        let input = "Dec 31 23:59:59 host kernel[0]: old\nJan  1 00:00:00 host kernel[0]: new\n";
+        let parser = LineParser::new().with_years(YearResolver::starting_in(2016));
+        let timestamps = parse_lines_with(input.as_bytes(), parser)
+            .map(|line| line.unwrap().entry.unwrap().timestamp.unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            timestamps,
+            vec!["2016-12-31 23:59:59", "2017-01-01 00:00:00"]
+        );
+    }
+
+    #[test]
+    fn parsed_entries_get_utc_timestamps() {
+        let input = "Jul  1 09:00:55 calvisitor-10-105-160-95 kernel[0]: a\n";
+        let parser = LineParser::new()
+            .with_years(YearResolver::starting_in(2017))
+            .with_zones(ZoneConverter::new("America/Los_Angeles".parse().unwrap()));
+        let entry = parse_lines_with(input.as_bytes(), parser)
+            .next()
+            .unwrap()
+            .unwrap()
+            .entry
+            .unwrap();
+        assert_eq!(entry.timestamp.unwrap().to_string(), "2017-07-01 09:00:55");
+        assert_eq!(
+            entry.timestamp_utc.unwrap().to_rfc3339(),
+            "2017-07-01T16:00:55+00:00"
+        );
+    }
+
+    #[test]
+    fn rfc5424_entries_keep_their_own_timestamps() {
+        let input = "<14>1 2024-07-01T09:00:55+02:00 host app 7 - - started\n";
+        let parser = LineParser::new()
+            .with_format(LogFormat::Rfc5424)
+            .with_years(YearResolver::starting_in(2017))
+            .with_zones(ZoneConverter::new("America/Los_Angeles".parse().unwrap()));
+        let entry = parse_lines_with(input.as_bytes(), parser)
+            .next()
+            .unwrap()
+            .unwrap()
+            .entry
+            .unwrap();
+        assert_eq!(entry.timestamp.unwrap().to_string(), "2024-07-01 09:00:55");
+        assert_eq!(
+            entry.timestamp_utc.unwrap().to_rfc3339(),
+            "2024-07-01T07:00:55+00:00"
+        );
+    }
+
+    #[test]
+    fn json_arrays_are_split_into_lines() {
+        let input = "[{\n  \"timestamp\" : \"2024-07-01 09:00:55-0700\",\n  \"eventMessage\" : \"a, [b] {c} \\\"d\\\"\"\n},{\n  \"backtrace\" : {\"frames\" : [1, 2]}\n}]\n";
+        let reader = split_json_array(Box::new(input.as_bytes())).unwrap();
+        let lines = reader.lines().collect::<io::Result<Vec<_>>>().unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                r#"{"timestamp":"2024-07-01 09:00:55-0700","eventMessage":"a, [b] {c} \"d\""}"#,
+                r#"{"backtrace":{"frames":[1,2]}}"#,
+            ]
+        );
+
+        for input in ["Jul  1 09:00:55 host kernel[0]: x\n", "[INFO] not json\n"] {
+            let mut reader = split_json_array(Box::new(input.as_bytes())).unwrap();
+            let mut all = String::new();
+            reader.read_to_string(&mut all).unwrap();
+            assert_eq!(all, input);
+        }
+    }
+
+    #[test]
+    fn malformed_json_arrays_are_errors() {
+        for (input, complete, message) in [
+            ("[{\"a\":1}}]\n", 0, "unbalanced brackets"),
+            ("[{\"a\":1},{\"b\":[2]]}]\n", 1, "unbalanced brackets"),
+            ("[{\"a\":1}]]\n", 1, "after the closing `]`"),
+            ("[{\"a\":1}] {\"b\":2}\n", 1, "after the closing `]`"),
+        ] {
+            let reader = split_json_array(Box::new(input.as_bytes())).unwrap();
+            let mut lines = reader.lines();
+            for _ in 0..complete {
+                assert_eq!(lines.next().unwrap().unwrap(), r#"{"a":1}"#, "{input}");
+            }
+            let err = lines.next().unwrap().unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert!(err.to_string().contains(message), "{input}: {err}");
+        }
+
+        let input = "[{\"a\":1}]\n \n";
+        let reader = split_json_array(Box::new(input.as_bytes())).unwrap();
+        let lines = reader.lines().collect::<io::Result<Vec<_>>>().unwrap();
+        assert_eq!(lines, [r#"{"a":1}"#]);
+    }
+
+    #[test]
+    fn journal_entries_are_shown_in_the_host_zone() {
+        let input = r#"{"__REALTIME_TIMESTAMP":"1719824455000000","_HOSTNAME":"buildbox-01","_COMM":"make","MESSAGE":"done"}"#;
+        let parser = LineParser::new()
+            .with_format(LogFormat::Journal)
+            .with_zones(ZoneConverter::new("Europe/Berlin".parse().unwrap()));
+        let entry = parse_lines_with(input.as_bytes(), parser)
+            .next()
+            .unwrap()
+            .unwrap()
+            .entry
+            .unwrap();
+        assert_eq!(entry.time.to_string(), "11:00:55");
+        assert_eq!(entry.timestamp.unwrap().to_string(), "2024-07-01 11:00:55");
+        assert_eq!(
+            entry.timestamp_utc.unwrap().to_rfc3339(),
+            "2024-07-01T09:00:55+00:00"
+        );
+    }
+
+    #[test]
+    fn sampled_lines_are_read_again() {
+        let input: Box<dyn BufRead> = Box::new(&b"one\ntwo\r\nthree\nfour"[..]);
+        let (sample, mut reader) = sample_lines(input, 2).unwrap();
+        assert_eq!(sample, vec!["one", "two"]);
+        let mut all = String::new();
+        io::Read::read_to_string(&mut reader, &mut all).unwrap();
+        assert_eq!(all, "one\ntwo\r\nthree\nfour");
+
+        let input: Box<dyn BufRead> = Box::new(&b"only"[..]);
+        let (sample, _) = sample_lines(input, 100).unwrap();
+        assert_eq!(sample, vec!["only"]);
+    }
+
+    #[test]
+    fn parsed_lines_are_numbered_from_one() {
+        let input = "garbage\nJul  1 09:00:55 host kernel[0]: first\n";
+        let lines = parse_lines(input.as_bytes())
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].text, "garbage");
+        assert_eq!(lines[0].entry.as_ref().unwrap_err().line_number, 1);
+        assert_eq!(lines[1].line_number, 2);
+        assert_eq!(lines[1].entry.as_ref().unwrap().message, "first");
+    }
+
+    #[test]
+    fn invalid_utf8_is_replaced_not_fatal() {
+        let input = b"Jul  1 09:00:55 host kernel[0]: caf\xc3\xa9 \xff bad\r\n\
+                      Jul  1 09:00:56 host kernel[0]: next\n";
+        let messages = parse_lines(&input[..])
+            .map(|line| line.unwrap().entry.unwrap().message.into_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(messages, vec!["caf\u{e9} \u{fffd} bad", "next"]);
+    }
+
+    const CRASH: &str = "Jul  1 09:00:55 host ReportCrash[512]: Thread 0 Crashed:\n\
+                         \t0   libsystem_kernel.dylib  0x00007fff 0x7fff + 10\n\
+                         \n\
+                         Binary Images:\n\
+                         Jul  1 09:00:56 host kernel[0]: next\n\
+                         --- last message repeated 2 times ---\n";
+
+    #[test]
+    fn continuation_lines_join_the_entry_before() {
+        let lines = parse_lines(CRASH.as_bytes())
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].line_number, 1);
+        assert_eq!(lines[0].continuations, 3);
+        assert_eq!(lines[0].text.lines().count(), 4);
+        assert_eq!(
+            lines[0].entry.as_ref().unwrap().message,
+            "Thread 0 Crashed:\n\t0   libsystem_kernel.dylib  0x00007fff 0x7fff + 10\n\nBinary Images:"
+        );
+        assert_eq!(lines[1].line_number, 5);
+        assert_eq!(lines[1].continuations, 0);
+        assert_eq!(lines[2].line_number, 6);
+        assert_eq!(lines[2].occurrences, 2);
+        assert_eq!(lines[2].entry.as_ref().unwrap().message, "next");
+    }
+
+    #[test]
+    fn continuations_are_not_joined_to_rejected_lines() {
+        let input = "garbage\n  indented\n";
+        let lines = parse_lines(input.as_bytes())
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.entry.is_err()));
+
+        let parser = LineParser::new().join_continuations(false);
+        let lines = parse_lines_with(CRASH.as_bytes(), parser)
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(
+            lines[0].entry.as_ref().unwrap().message,
+            "Thread 0 Crashed:"
+        );
+        assert!(lines[1].entry.is_err());
+    }
+
+    #[test]
+    fn repeat_marker_repeats_the_whole_multi_line_entry() {
+        let input =
+            "Jul  1 09:00:55 host app[1]: one\n  two\n--- last message repeated 3 times ---\n";
+        let mut parser = LineParser::new();
+        let mut lines = Vec::new();
+        for text in input.lines() {
+            lines.extend(parser.push(text).map(ParsedLine::into_owned));
+        }
+        assert_eq!(lines.len(), 1);
+        lines.extend(parser.finish().map(ParsedLine::into_owned));
+        assert_eq!(lines[1].occurrences, 3);
+        assert_eq!(lines[1].text, "Jul  1 09:00:55 host app[1]: one\n  two");
+        assert_eq!(lines[1].entry.as_ref().unwrap().message, "one\n  two");
+        assert!(parser.finish().is_none());
+    }
+}
@@ -0,0 +1,294 @@
+use std::borrow::Cow;
+use std::io::{self, BufRead, Read};
+
+use chrono::{DateTime, Datelike, Timelike};
+use serde_json::{Map, Value};
+
+use crate::entry::{LogEntry, Month, TimeOfDay};
+use crate::error::{ParseError, ParseErrorKind};
+use crate::priority::{Facility, Priority, Severity};
+
+/// Parses one entry of `journalctl -o json` output:
+///
+/// ```text
+/// {"__REALTIME_TIMESTAMP":"1719824455123456","_HOSTNAME":"buildbox-01","SYSLOG_IDENTIFIER":"sshd","_PID":"512","PRIORITY":"6","MESSAGE":"Accepted publickey for ci",...}
+/// ```
+///
+/// `SYSLOG_IDENTIFIER` names the process, falling back to `_COMM`; a missing
+/// hostname or process is recorded as `-` and a missing pid as 0. `PRIORITY`
+/// gives the severity, and `SYSLOG_FACILITY` the facility (`user` when
+/// absent). `__REALTIME_TIMESTAMP` is an instant, so only `timestamp_utc` is
+/// set; month, day and time are in UTC until [`crate::input::LineParser`]
+/// moves them to the host's zone. Fields journald stored as byte arrays are
+/// read as lossy UTF-8, and repeated fields by their first value.
+pub fn parse_journal(line: &str) -> Result<LogEntry<'static>, ParseError> {
+    let fields: Map<String, Value> = serde_json::from_str(line).map_err(ParseError::from_json)?;
+    let field = |name: &str| fields.get(name).and_then(field_text);
+    let offset_of = |value: &str| line.find(value).unwrap_or(0);
+
+    let micros = field("__REALTIME_TIMESTAMP")
+        .ok_or_else(|| ParseError::new(ParseErrorKind::MissingTimestamp, 0))?;
+    let utc = micros
+        .parse()
+        .ok()
+        .and_then(DateTime::from_timestamp_micros)
+        .ok_or_else(|| {
+            ParseError::new(
+                ParseErrorKind::InvalidTimestamp(micros.clone()),
+                offset_of(&micros),
+            )
+        })?;
+    let pid = match field("_PID") {
+        Some(pid) => pid.parse().map_err(|_| {
+            ParseError::new(ParseErrorKind::InvalidPid(pid.clone()), offset_of(&pid))
+        })?,
+        None => 0,
+    };
+    let priority = match field("PRIORITY") {
+        Some(value) => Some(
+            parse_priority(&value, field("SYSLOG_FACILITY")).ok_or_else(|| {
+                ParseError::new(
+                    ParseErrorKind::InvalidPriority(value.clone()),
+                    offset_of(&value),
+                )
+            })?,
+        ),
+        None => None,
+    };
+
+    let time = utc.naive_utc();
+    Ok(LogEntry {
+        month: Month::ALL[time.month0() as usize],
+        day: time.day() as u8,
+        time: TimeOfDay {
+            hour: time.hour() as u8,
+            minute: time.minute() as u8,
+            second: time.second() as u8,
+        },
+        hostname: field("_HOSTNAME").map_or("-".into(), Cow::Owned),
+        process: field("SYSLOG_IDENTIFIER")
+            .or_else(|| field("_COMM"))
+            .map_or("-".into(), Cow::Owned),
+        pid,
+        message: field("MESSAGE").unwrap_or_default().into(),
+        timestamp: None,
+        timestamp_utc: Some(utc),
+        priority,
+        rfc5424: None,
+        unified: None,
+    })
+}
+
+/// A field's value as text: strings as they are, byte arrays decoded, and
+/// the first value of a repeated field. `None` for `null`, which journalctl
+/// writes for values too large to show.
+fn field_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        Value::Array(items) if items.iter().all(Value::is_u64) => {
+            let bytes = items
+                .iter()
+                .filter_map(|item| item.as_u64().and_then(|byte| u8::try_from(byte).ok()))
+                .collect::<Vec<_>>();
+            Some(String::from_utf8_lossy(&bytes).into_owned())
+        }
+        Value::Array(items) => items.first().and_then(field_text),
+        _ => None,
+    }
+}
+
+fn parse_priority(severity: &str, facility: Option<String>) -> Option<Priority> {
+    let severity = *Severity::ALL.get(severity.parse::<usize>().ok()?)?;
+    let facility = match facility {
+        Some(facility) => *Facility::ALL.get(facility.parse::<usize>().ok()?)?,
+        None => Facility::User,
+    };
+    Some(Priority { facility, severity })
+}
+
+/// Whether `header` starts a `journalctl -o export` stream, whose first
+/// record opens with its cursor or timestamp.
+pub fn is_export(header: &[u8]) -> bool {
+    header.starts_with(b"__CURSOR=") || header.starts_with(b"__REALTIME_TIMESTAMP=")
+}
+
+/// Reads a `journalctl -o export` stream as `-o json` lines, one per record.
+///
+/// Export records are runs of `NAME=value` lines ended by a blank line;
+/// values that are binary or contain newlines are written as `NAME`, a
+/// little-endian 64-bit length and the raw bytes instead. Repeated fields
+/// become arrays, as in `-o json`.
+pub struct ExportReader {
+    inner: Box<dyn BufRead>,
+    line: Vec<u8>,
+    position: usize,
+}
+
+impl ExportReader {
+    pub fn new(inner: Box<dyn BufRead>) -> Self {
+        Self {
+            inner,
+            line: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// The next record's fields, or `None` at the end of the stream.
+    fn next_record(&mut self) -> io::Result<Option<Map<String, Value>>> {
+        let mut fields = Map::new();
+        loop {
+            let mut line = Vec::new();
+            if self.inner.read_until(b'\n', &mut line)? == 0 {
+                return Ok((!fields.is_empty()).then_some(fields));
+            }
+            if line.last() == Some(&b'\n') {
+                line.pop();
+            }
+            if line.is_empty() {
+                if fields.is_empty() {
+                    continue;
+                }
+                return Ok(Some(fields));
+            }
+            let (name, value) = match line.iter().position(|&byte| byte == b'=') {
+                Some(equals) => (&line[..equals], line[equals + 1..].to_vec()),
+                None => (&line[..], self.read_binary_value()?),
+            };
+            let name = String::from_utf8_lossy(name).into_owned();
+            let value = Value::String(String::from_utf8_lossy(&value).into_owned());
+            match fields.get_mut(&name) {
+                Some(Value::Array(values)) => values.push(value),
+                Some(first) => *first = Value::Array(vec![first.take(), value]),
+                None => {
+                    fields.insert(name, value);
+                }
+            }
+        }
+    }
+
+    fn read_binary_value(&mut self) -> io::Result<Vec<u8>> {
+        let mut size = [0; 8];
+        self.inner.read_exact(&mut size)?;
+        let size = u64::from_le_bytes(size);
+        let mut value = Vec::new();
+        (&mut self.inner).take(size).read_to_end(&mut value)?;
+        if value.len() as u64 != size {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let mut newline = [0];
+        self.inner.read_exact(&mut newline)?;
+        Ok(value)
+    }
+}
+
+impl Read for ExportReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.line.len() {
+            let Some(fields) = self.next_record()? else {
+                return Ok(0);
+            };
+            self.line = serde_json::to_vec(&fields)?;
+            self.line.push(b'\n');
+            self.position = 0;
+        }
+        let count = out.len().min(self.line.len() - self.position);
+        out[..count].copy_from_slice(&self.line[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTRY: &str = r#"{"__CURSOR":"s=1;i=2","__REALTIME_TIMESTAMP":"1719824455123456","__MONOTONIC_TIMESTAMP":"42","_BOOT_ID":"b","PRIORITY":"6","SYSLOG_FACILITY":"4","_UID":"0","_COMM":"sshd","SYSLOG_IDENTIFIER":"sshd","_PID":"512","_HOSTNAME":"buildbox-01","_TRANSPORT":"syslog","MESSAGE":"Accepted publickey for ci from 10.0.0.5"}"#;
+
+    #[test]
+    fn parse_json_entry() {
+        let entry = parse_journal(ENTRY).unwrap();
+        assert_eq!(entry.month, Month::Jul);
+        assert_eq!(entry.day, 1);
+        assert_eq!(entry.time.to_string(), "09:00:55");
+        assert_eq!(entry.hostname, "buildbox-01");
+        assert_eq!(entry.process, "sshd");
+        assert_eq!(entry.pid, 512);
+        assert_eq!(entry.message, "Accepted publickey for ci from 10.0.0.5");
+        assert_eq!(entry.timestamp, None);
+        assert_eq!(
+            entry.timestamp_utc.unwrap().to_rfc3339(),
+            "2024-07-01T09:00:55.123456+00:00"
+        );
+        let priority = entry.priority.unwrap();
+        assert_eq!(priority.facility, Facility::Auth);
+        assert_eq!(priority.severity, Severity::Info);
+    }
+
+    #[test]
+    fn fields_fall_back_and_decode() {
+        let entry = parse_journal(
+            r#"{"__REALTIME_TIMESTAMP":"1719824455000000","_COMM":"make","PRIORITY":"3","MESSAGE":[104,105,255]}"#,
+        )
+        .unwrap();
+        assert_eq!(entry.process, "make");
+        assert_eq!(entry.hostname, "-");
+        assert_eq!(entry.pid, 0);
+        assert_eq!(entry.message, "hi\u{fffd}");
+        assert_eq!(entry.priority.unwrap().facility, Facility::User);
+
+        let entry = parse_journal(
+            r#"{"__REALTIME_TIMESTAMP":"1719824455000000","SYSLOG_IDENTIFIER":["a","b"],"MESSAGE":null}"#,
+        )
+        .unwrap();
+        assert_eq!(entry.process, "a");
+        assert_eq!(entry.message, "");
+        assert_eq!(entry.priority, None);
+    }
+
+    #[test]
+    fn rejected_journal_lines() {
+        let kind = |line: &str| parse_journal(line).unwrap_err().kind.name();
+        assert_eq!(kind(r#"{"MESSAGE":"x"}"#), "missing_timestamp");
+        assert_eq!(
+            kind(r#"{"__REALTIME_TIMESTAMP":"soon"}"#),
+            "invalid_timestamp"
+        );
+        assert_eq!(
+            kind(r#"{"__REALTIME_TIMESTAMP":"1","_PID":"x"}"#),
+            "invalid_pid"
+        );
+        assert_eq!(
+            kind(r#"{"__REALTIME_TIMESTAMP":"1","PRIORITY":"8"}"#),
+            "invalid_priority"
+        );
+        assert_eq!(kind("Jul  1 09:00:55 host kernel[0]: x"), "invalid_json");
+    }
+
+    #[test]
+    fn export_records_become_json_lines() {
+        let mut export = b"__CURSOR=s=1\n__REALTIME_TIMESTAMP=1719824455123456\n_HOSTNAME=buildbox-01\n\
+                           SYSLOG_IDENTIFIER=sshd\n_PID=512\nPRIORITY=6\nMESSAGE=one=two\n\n\
+                           __CURSOR=s=2\n__REALTIME_TIMESTAMP=1719824456000000\n_COMM=make\nTAG=a\nTAG=b\nMESSAGE\n"
+            .to_vec();
+        export.extend(9u64.to_le_bytes());
+        export.extend(b"two\nlines\n\n");
+        assert!(is_export(&export));
+
+        let reader = ExportReader::new(Box::new(io::Cursor::new(export)));
+        let lines = io::BufReader::new(reader)
+            .lines()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(lines.len(), 2);
+        let first = parse_journal(&lines[0]).unwrap();
+        assert_eq!(first.hostname, "buildbox-01");
+        assert_eq!(first.process, "sshd");
+        assert_eq!(first.message, "one=two");
+        let second = parse_journal(&lines[1]).unwrap();
+        assert_eq!(second.process, "make");
+        assert_eq!(second.message, "two\nlines");
+        let json: Value = serde_json::from_str(&lines[1]).unwrap();
+        assert_eq!(json["TAG"], serde_json::json!(["a", "b"]));
+    }
+}
@@ -0,0 +1,487 @@
+use std::borrow::Cow;
+
+/// Characters that stay inside a keyword when letters or digits surround
+/// them, so `com.apple.cdscheduler` and `wl_update_tcpkeep_seq` are single
+/// keywords. At either end of a keyword they are trimmed off.
+const JOINERS: [char; 4] = ['.', '_', '-', '\''];
+
+/// Punctuation that ends a clause. Phrases do not reach across it.
+const CLAUSE_BREAKS: [char; 14] = [
+    ':', ';', ',', '=', '(', ')', '[', ']', '{', '}', '<', '>', '|', '"',
+];
+
+/// Hex strings at least this long that contain a digit, such as UUIDs, are
+/// identifiers rather than words.
+pub(crate) const MIN_HEX_ID_DIGITS: usize = 8;
+
+/// Splits messages into the keywords counted in `top_keywords`.
+///
+/// Words are separated by whitespace, punctuation and brackets, so
+/// `WWEN[enabled]` yields `wwen` and `enabled`, and `state:` is `state`.
+/// Keywords are lowercased, apostrophes are dropped (`isn't` becomes `isnt`,
+/// as the stopword list spells it), and by default pure numbers (`11`,
+/// `620701.011328`, `10.105.160.95`), hex addresses (`0x0`) and UUIDs are
+/// skipped, as are keywords shorter than two characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tokenizer {
+    min_length: usize,
+    keep_numbers: bool,
+    keep_hex: bool,
+    lowercase: bool,
+}
+
+/// An item of [`Tokenizer::scan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token<'a> {
+    Word(Cow<'a, str>),
+    /// The end of a run of adjacent keywords.
+    Break,
+}
+
+impl Default for Tokenizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tokenizer {
+    pub fn new() -> Self {
+        Self {
+            min_length: 2,
+            keep_numbers: false,
+            keep_hex: false,
+            lowercase: true,
+        }
+    }
+
+    /// Skips keywords with fewer than `min_length` characters.
+    pub fn with_min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    /// Counts numbers, including dotted ones such as versions and IPv4
+    /// addresses, as keywords.
+    pub fn keep_numbers(mut self, keep: bool) -> Self {
+        self.keep_numbers = keep;
+        self
+    }
+
+    /// Counts `0x` addresses and UUID-like hex identifiers as keywords.
+    pub fn keep_hex(mut self, keep: bool) -> Self {
+        self.keep_hex = keep;
+        self
+    }
+
+    /// Leaves keywords in the case they were written in.
+    pub fn lowercase(mut self, lowercase: bool) -> Self {
+        self.lowercase = lowercase;
+        self
+    }
+
+    /// The keywords of `message`, in order. They borrow from `message` unless
+    /// normalizing changed them.
+    pub fn tokens<'a>(&'a self, message: &'a str) -> impl Iterator<Item = Cow<'a, str>> + 'a {
+        self.scan(message).filter_map(|token| match token {
+            Token::Word(word) => Some(word),
+            Token::Break => None,
+        })
+    }
+
+    /// The keywords of `message` with a [`Token::Break`] wherever a phrase
+    /// cannot continue: at clause punctuation (`:`, `,`, `=`, brackets), at
+    /// a full stop, and in place of a skipped word such as a number. So
+    /// `Thermal pressure state: 1 Memory pressure state: 0` has the runs
+    /// `thermal pressure state` and `memory pressure state`. Breaks may
+    /// repeat, and the end of the message is not marked.
+    pub fn scan<'a>(&'a self, message: &'a str) -> Scan<'a> {
+        Scan {
+            tokenizer: self,
+            rest: message,
+            pending: None,
+            sentence_end: false,
+        }
+    }
+
+    /// The token for one word, trimmed of joiners, and whether a full stop
+    /// ended it.
+    fn token<'a>(&self, raw: &'a str) -> Option<(Token<'a>, bool)> {
+        let word = raw.trim_matches(JOINERS);
+        if word.is_empty() {
+            return None;
+        }
+        let shape = Shape::of(word);
+        let skipped = shape.length < self.min_length
+            || (!self.keep_numbers && shape.is_number())
+            || (!self.keep_hex && shape.is_hex_id(word));
+        if skipped {
+            return Some((Token::Break, false));
+        }
+        let word = if shape.any & APOSTROPHE != 0 {
+            Cow::Owned(word.replace('\'', ""))
+        } else {
+            Cow::Borrowed(word)
+        };
+        let word = if self.lowercase && shape.any & UPPER != 0 {
+            Cow::Owned(if word.is_ascii() {
+                word.to_ascii_lowercase()
+            } else {
+                word.to_lowercase()
+            })
+        } else {
+            word
+        };
+        Some((Token::Word(word), raw.ends_with('.')))
+    }
+}
+
+/// Iterator returned by [`Tokenizer::scan`].
+pub struct Scan<'a> {
+    tokenizer: &'a Tokenizer,
+    rest: &'a str,
+    /// A word found after a clause break, returned after the break.
+    pending: Option<Token<'a>>,
+    /// The last word ended a sentence, so a break follows it.
+    sentence_end: bool,
+}
+
+impl<'a> Iterator for Scan<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        if let Some(token) = self.pending.take() {
+            return Some(token);
+        }
+        if self.sentence_end {
+            self.sentence_end = false;
+            return Some(Token::Break);
+        }
+        let mut clause_break = false;
+        loop {
+            let start = skip_while(self.rest, 0, |class| {
+                clause_break |= class & CLAUSE != 0;
+                class & WORD == 0
+            });
+            if start == self.rest.len() {
+                self.rest = "";
+                return None;
+            }
+            let end = skip_while(self.rest, start, |class| class & WORD != 0);
+            let raw = &self.rest[start..end];
+            self.rest = &self.rest[end..];
+            let Some((token, sentence_end)) = self.tokenizer.token(raw) else {
+                continue;
+            };
+            self.sentence_end = sentence_end;
+            if clause_break {
+                self.pending = Some(token);
+                return Some(Token::Break);
+            }
+            return Some(token);
+        }
+    }
+}
+
+const WORD: u8 = 1;
+const CLAUSE: u8 = 1 << 1;
+const DIGIT: u8 = 1 << 2;
+/// Digits and joiners, what numbers are made of.
+const NUMERIC: u8 = 1 << 3;
+/// Hex digits and dashes, what UUIDs are made of.
+const HEXISH: u8 = 1 << 4;
+const UPPER: u8 = 1 << 5;
+const APOSTROPHE: u8 = 1 << 6;
+
+/// Classes of the ASCII characters, so scanning needs one lookup per byte.
+const ASCII_CLASSES: [u8; 128] = {
+    let mut classes = [0; 128];
+    let mut byte = 0;
+    while byte < 128 {
+        let c = byte as u8 as char;
+        let mut class = 0;
+        if c.is_ascii_alphanumeric() {
+            class |= WORD;
+        }
+        if c.is_ascii_digit() {
+            class |= DIGIT | NUMERIC;
+        }
+        if c.is_ascii_hexdigit() || c == '-' {
+            class |= HEXISH;
+        }
+        if c.is_ascii_uppercase() {
+            class |= UPPER;
+        }
+        classes[byte] = class;
+        byte += 1;
+    }
+    let mut i = 0;
+    while i < JOINERS.len() {
+        classes[JOINERS[i] as usize] |= WORD | NUMERIC;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < CLAUSE_BREAKS.len() {
+        classes[CLAUSE_BREAKS[i] as usize] |= CLAUSE;
+        i += 1;
+    }
+    classes['\'' as usize] |= APOSTROPHE;
+    classes
+};
+
+/// The class of any character: letters and digits outside ASCII are word
+/// characters, and uppercase ones are `UPPER`.
+fn class_of(c: char) -> u8 {
+    match c {
+        c if c.is_ascii() => ASCII_CLASSES[c as usize],
+        c if c.is_uppercase() => WORD | UPPER,
+        c if c.is_alphanumeric() => WORD,
+        _ => 0,
+    }
+}
+
+/// The byte offset of the first character at or after `from` whose class
+/// `skip` rejects, or the end of `text`.
+fn skip_while(text: &str, from: usize, mut skip: impl FnMut(u8) -> bool) -> usize {
+    let bytes = text.as_bytes();
+    let mut i = from;
+    while i < bytes.len() {
+        let (class, width) = match bytes[i] {
+            byte if byte.is_ascii() => (ASCII_CLASSES[byte as usize], 1),
+            _ => {
+                let c = text[i..].chars().next().unwrap_or_default();
+                (class_of(c), c.len_utf8())
+            }
+        };
+        if !skip(class) {
+            return i;
+        }
+        i += width;
+    }
+    bytes.len()
+}
+
+/// What one pass over a word tells about it.
+struct Shape {
+    /// Characters, not counting apostrophes.
+    length: usize,
+    /// Classes every character has.
+    all: u8,
+    /// Classes some character has.
+    any: u8,
+}
+
+impl Shape {
+    fn of(word: &str) -> Self {
+        let mut shape = Shape {
+            length: 0,
+            all: !0,
+            any: 0,
+        };
+        if word.is_ascii() {
+            for &byte in word.as_bytes() {
+                let class = ASCII_CLASSES[byte as usize];
+                shape.all &= class;
+                shape.any |= class;
+            }
+            shape.length = word.len();
+        } else {
+            for c in word.chars() {
+                let class = class_of(c);
+                shape.all &= class;
+                shape.any |= class;
+                shape.length += 1;
+            }
+        }
+        if shape.any & APOSTROPHE != 0 {
+            shape.length -= word.matches('\'').count();
+        }
+        shape
+    }
+
+    /// Only digits and joiners: `11`, `620701.011328`, `2017-07-01`.
+    fn is_number(&self) -> bool {
+        self.all & NUMERIC != 0
+    }
+
+    /// `0x7fff`, or a run of hex digits and dashes with at least one digit,
+    /// such as `DB05755C-483D-44B7-B93B-ED06E57FF420`.
+    fn is_hex_id(&self, word: &str) -> bool {
+        if word.starts_with("0x") || word.starts_with("0X") {
+            return word.len() > 2 && word[2..].bytes().all(|b| b.is_ascii_hexdigit());
+        }
+        self.all & HEXISH != 0
+            && self.any & DIGIT != 0
+            && word.bytes().filter(u8::is_ascii_hexdigit).count() >= MIN_HEX_ID_DIGITS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(tokenizer: &Tokenizer, message: &str) -> Vec<String> {
+        tokenizer.tokens(message).map(Cow::into_owned).collect()
+    }
+
+    #[test]
+    fn split_on_punctuation_and_brackets() {
+        let tokenizer = Tokenizer::new();
+        assert_eq!(
+            tokens(
+                &tokenizer,
+                "ARPT: 620701.011328: AirPort_Brcm43xx::syncPowerState: WWEN[enabled]"
+            ),
+            [
+                "arpt",
+                "airport_brcm43xx",
+                "syncpowerstate",
+                "wwen",
+                "enabled"
+            ]
+        );
+        assert_eq!(
+            tokens(
+                &tokenizer,
+                "Thermal pressure state: 1 Memory pressure state: 0"
+            ),
+            [
+                "thermal", "pressure", "state", "memory", "pressure", "state"
+            ]
+        );
+    }
+
+    #[test]
+    fn numbers_and_hex_addresses_are_dropped() {
+        let tokenizer = Tokenizer::new();
+        assert_eq!(
+            tokens(
+                &tokenizer,
+                "IOThunderboltSwitch<0>(0x0)::listenerCallback - Thunderbolt HPD packet for route = 0x0 port = 11 unplug = 0"
+            ),
+            [
+                "iothunderboltswitch",
+                "listenercallback",
+                "thunderbolt",
+                "hpd",
+                "packet",
+                "for",
+                "route",
+                "port",
+                "unplug"
+            ]
+        );
+        assert_eq!(
+            tokens(
+                &tokenizer,
+                "enabling pid=30298 for plug-in com.apple.ncplugin.weather(1.0) 131FE7ED-87F7-471D-8797-C11107688DF7"
+            ),
+            [
+                "enabling",
+                "pid",
+                "for",
+                "plug-in",
+                "com.apple.ncplugin.weather"
+            ]
+        );
+    }
+
+    #[test]
+    fn paths_and_contractions() {
+        let tokenizer = Tokenizer::new();
+        assert_eq!(
+            tokens(
+                &tokenizer,
+                "The specified service did not ship in the requestor's bundle, origin = /System/Library/WebKit.framework"
+            ),
+            [
+                "the",
+                "specified",
+                "service",
+                "did",
+                "not",
+                "ship",
+                "in",
+                "the",
+                "requestors",
+                "bundle",
+                "origin",
+                "system",
+                "library",
+                "webkit.framework"
+            ]
+        );
+        assert_eq!(
+            tokens(
+                &tokenizer,
+                "the start time isn't for 59 seconds.  Ignoring."
+            ),
+            ["the", "start", "time", "isnt", "for", "seconds", "ignoring"]
+        );
+    }
+
+    #[test]
+    fn runs_stop_at_clauses_and_skipped_words() {
+        let tokenizer = Tokenizer::new();
+        let runs = |message| {
+            let mut runs = Vec::new();
+            let mut run = Vec::new();
+            for token in tokenizer.scan(message) {
+                match token {
+                    Token::Word(word) => run.push(word),
+                    Token::Break if run.len() >= 2 => runs.push(std::mem::take(&mut run).join(" ")),
+                    Token::Break => run.clear(),
+                }
+            }
+            if run.len() >= 2 {
+                runs.push(run.join(" "));
+            }
+            runs
+        };
+        assert_eq!(
+            runs("Thermal pressure state: 1 Memory pressure state: 0"),
+            ["thermal pressure state", "memory pressure state"]
+        );
+        assert_eq!(
+            runs("the start time isn't for 59 seconds.  Ignoring."),
+            ["the start time isnt for"]
+        );
+        assert_eq!(
+            runs("Sleep [EH:0-0x0 ...] reason: Clamshell Sleep. Wake due to RTC"),
+            ["clamshell sleep", "wake due to rtc"]
+        );
+    }
+
+    #[test]
+    fn words_outside_ascii() {
+        let tokenizer = Tokenizer::new();
+        assert_eq!(
+            tokens(&tokenizer, "Über “Café” → naïve résumé №5"),
+            ["über", "café", "naïve", "résumé"]
+        );
+    }
+
+    #[test]
+    fn lowercase_words_are_borrowed() {
+        let tokenizer = Tokenizer::new();
+        let tokens = tokenizer.tokens("wake reason: RTC").collect::<Vec<_>>();
+        assert!(matches!(tokens[0], Cow::Borrowed("wake")));
+        assert!(matches!(tokens[2], Cow::Owned(ref word) if word == "rtc"));
+    }
+
+    #[test]
+    fn configured_tokenizer_keeps_more() {
+        let tokenizer = Tokenizer::new()
+            .with_min_length(1)
+            .keep_numbers(true)
+            .keep_hex(true)
+            .lowercase(false);
+        assert_eq!(
+            tokens(&tokenizer, "route = 0x0 port = 11 from 10.105.160.95: A"),
+            ["route", "0x0", "port", "11", "from", "10.105.160.95", "A"]
+        );
+        assert_eq!(
+            tokens(&Tokenizer::new().with_min_length(4), "USB is up: ok"),
+            Vec::<String>::new()
+        );
+    }
+}
@@ -0,0 +1,25 @@
+// Rust Bytes Challenge Issue #93 Log Analyzer
+
+pub mod convert;
+pub mod entry;
+pub mod error;
+pub mod follow;
+pub mod format;
+pub mod input;
+pub mod journal;
+pub mod keywords;
+pub mod pattern;
+pub mod priority;
+pub mod rfc5424;
+#[cfg(test)]
+mod scratch;
+pub mod stopwords;
+pub mod summary;
+pub mod templates;
+pub mod timestamp;
+pub mod unified;
+
+pub use entry::{LogEntry, Month, TimeOfDay};
+pub use error::{ParseError, ParseErrorKind};
+pub use format::LogFormat;
+pub use summary::{Analyzer, Stats, Summary};
@@ -1,1130 +1,1114 @@
 // Rust Bytes Challenge Issue #93 Log Analyzer
 
-use std::collections::{HashMap, HashSet};
-use std::fs::File;
-use std::io;
-use std::io::BufRead;
-use std::path::Path;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use log_analyzer::convert::{self, CSV_HEADER};
+use log_analyzer::follow::Follower;
+use log_analyzer::format::{self, Detection};
+use log_analyzer::input::{self, LineParser, ParsedLine};
+use log_analyzer::keywords::Tokenizer;
+use log_analyzer::pattern;
+use log_analyzer::stopwords::{self, Preset, Stopwords};
+use log_analyzer::templates::TemplateMiner;
+use log_analyzer::timestamp::{self, YearResolver, Zone, ZoneConverter};
+use log_analyzer::{Analyzer, LogFormat, ParseError};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
-#[derive(Debug)]
-#[allow(dead_code)]
-struct Summary {
-    total_entries: usize,
-    by_process: Vec<(String, usize)>,
-    by_hostname: Vec<(String, usize)>,
-    most_frequent_process: String,
-    most_frequent_hostname: String,
-    top_keywords: Vec<String>,
+const DEFAULT_OUTPUT_PATH: &str = "summary.json";
+const DEFAULT_TOP_N: usize = 3;
+const DEFAULT_FOLLOW_INTERVAL: u64 = 2;
+const DEFAULT_SAMPLE_LINES: usize = 100;
+const DEFAULT_MIN_MATCH: f64 = 0.5;
+const DEFAULT_MIN_KEYWORD_LENGTH: usize = 2;
+const DEFAULT_STOPWORD_PRESET: &str = "english";
+const NO_STOPWORD_PRESET: &str = "none";
+const AUTO_FORMAT: &str = "auto";
+/// Rejected lines reported one by one per file; the rest are only counted.
+const MAX_REPORTED_REJECTIONS: usize = 10;
+
+/// Exit code for invalid arguments, as clap uses.
+const EXIT_USAGE: u8 = 2;
+/// Exit code when no known format matches an input (sysexits EX_DATAERR).
+const EXIT_DATA_ERROR: u8 = 65;
+/// Exit code when an input is missing or cannot be opened (sysexits EX_NOINPUT).
+const EXIT_NO_INPUT: u8 = 66;
+/// Exit code when reading an input or writing the output fails (sysexits EX_IOERR).
+const EXIT_IO_ERROR: u8 = 74;
+/// Exit code when a configuration file is malformed (sysexits EX_CONFIG).
+const EXIT_CONFIG: u8 = 78;
+
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "Per-process, per-host and keyword statistics for macOS/BSD syslog files"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Write the full summary document (summary.json by default)
+    Summary(SummaryArgs),
+    /// Print entries whose message contains a pattern
+    Grep(GrepArgs),
+    /// Print the top processes, hostnames and keywords
+    Stats(StatsArgs),
+    /// Re-emit parsed entries as NDJSON or CSV
+    Convert(ConvertArgs),
+    /// Keep a log open and report live statistics as lines are appended
+    Follow(FollowArgs),
+}
+
+#[derive(Debug, Args)]
+struct InputArgs {
+    /// Log files, directories (read recursively) or quoted globs such as
+    /// 'system.log*'; `-` or nothing reads stdin
+    inputs: Vec<PathBuf>,
+    /// Treat `last message repeated N times` markers as ordinary lines
+    /// instead of counting N more of the previous entry
+    #[arg(long)]
+    no_expand_repeats: bool,
+    /// Keep lines that are indented or do not start with a timestamp as
+    /// lines of their own instead of appending them to the entry before
+    #[arg(long)]
+    no_join_continuations: bool,
+    /// Year of the first entry in each input; by default inferred from the
+    /// file's modification time (today for stdin)
+    #[arg(long)]
+    year: Option<i32>,
+    #[command(flatten)]
+    zones: ZoneArgs,
+    #[command(flatten)]
+    line_format: FormatArgs,
+}
+
+impl InputArgs {
+    fn line_parser(&self, path: &Path, format: LogFormat) -> Result<LineParser, CliError> {
+        Ok(LineParser::new()
+            .expand_repeats(!self.no_expand_repeats)
+            .join_continuations(!self.no_join_continuations)
+            .with_format(format)
+            .with_years(year_resolver(self.year, path))
+            .with_zones(self.zones.converter(path)?))
+    }
+
+    fn paths(&self) -> Vec<PathBuf> {
+        if self.inputs.is_empty() {
+            vec![PathBuf::from(input::STDIN)]
+        } else {
+            self.inputs.clone()
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+struct SummaryArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    /// Where to write the summary; `-` for stdout
+    #[arg(short, long, default_value = DEFAULT_OUTPUT_PATH)]
+    output: PathBuf,
+    /// Number of keywords to report
+    #[arg(short = 'n', long, default_value_t = DEFAULT_TOP_N)]
+    top: usize,
+    #[command(flatten)]
+    keywords: KeywordArgs,
+    #[arg(short, long, value_enum, default_value_t = ReportFormat::Json)]
+    format: ReportFormat,
+}
+
+#[derive(Debug, Args)]
+struct GrepArgs {
+    /// Substring to look for in each entry's message
+    pattern: String,
+    #[command(flatten)]
+    input: InputArgs,
+    /// Match the pattern case-insensitively
+    #[arg(short, long)]
+    ignore_case: bool,
+    /// Only consider entries from this process
+    #[arg(long)]
+    process: Option<String>,
+    /// Only consider entries from this hostname
+    #[arg(long)]
+    host: Option<String>,
+    /// Where to write matches; stdout by default
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// `text` prints matching lines as they appear in the log
+    #[arg(short, long, value_enum, default_value_t = ReportFormat::Text)]
+    format: ReportFormat,
+}
+
+#[derive(Debug, Args)]
+struct StatsArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    /// Where to write the statistics; stdout by default
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Number of processes, hostnames and keywords to report
+    #[arg(short = 'n', long, default_value_t = DEFAULT_TOP_N)]
+    top: usize,
+    #[command(flatten)]
+    keywords: KeywordArgs,
+    #[arg(short, long, value_enum, default_value_t = ReportFormat::Text)]
+    format: ReportFormat,
+}
+
+#[derive(Debug, Args)]
+struct ConvertArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    /// Where to write the converted entries; stdout by default
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    #[arg(short, long, value_enum, default_value_t = ConvertFormat::Ndjson)]
+    format: ConvertFormat,
+}
+
+#[derive(Debug, Args)]
+struct FollowArgs {
+    /// Log file to follow; truncation and rename-based rotation are handled
+    path: PathBuf,
+    /// Seconds between reports, at least 1
+    #[arg(
+        short,
+        long,
+        default_value_t = DEFAULT_FOLLOW_INTERVAL,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    interval: u64,
+    /// Also count the lines already in the file
+    #[arg(long)]
+    from_start: bool,
+    /// Treat `last message repeated N times` markers as ordinary lines
+    #[arg(long)]
+    no_expand_repeats: bool,
+    /// Keep lines that are indented or do not start with a timestamp as
+    /// lines of their own instead of appending them to the entry before
+    #[arg(long)]
+    no_join_continuations: bool,
+    /// Year of the first entry; by default inferred from the file's
+    /// modification time
+    #[arg(long)]
+    year: Option<i32>,
+    #[command(flatten)]
+    zones: ZoneArgs,
+    #[command(flatten)]
+    line_format: FormatArgs,
+    /// File to rewrite on every report; reports are printed to stdout by default
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Number of processes, hostnames and keywords to report
+    #[arg(short = 'n', long, default_value_t = DEFAULT_TOP_N)]
+    top: usize,
+    #[command(flatten)]
+    keywords: KeywordArgs,
+    #[arg(short, long, value_enum, default_value_t = ReportFormat::Text)]
+    format: ReportFormat,
+}
+
+#[derive(Debug, Default, Args)]
+struct ZoneArgs {
+    /// Zone the timestamps were written in, as an IANA name or `local`
+    /// (the default); `PATH=ZONE` applies only to inputs under PATH.
+    /// May be repeated
+    #[arg(long = "timezone", value_name = "[PATH=]ZONE", value_parser = parse_zone_spec)]
+    timezones: Vec<ZoneSpec>,
+    /// File of `<hostname> <zone>` lines; these hosts' timestamps use their
+    /// own zone regardless of `--timezone`
+    #[arg(long, value_name = "FILE")]
+    host_timezones: Option<PathBuf>,
+}
+
+impl ZoneArgs {
+    /// Converter for one input: the last `--timezone` whose PATH contains
+    /// it, else the last bare `--timezone`, with host overrides on top.
+    fn converter(&self, path: &Path) -> Result<ZoneConverter, CliError> {
+        let for_path = self.timezones.iter().rev().find(|spec| {
+            spec.path
+                .as_ref()
+                .is_some_and(|prefix| path.starts_with(prefix))
+        });
+        let default = for_path
+            .or_else(|| self.timezones.iter().rev().find(|spec| spec.path.is_none()))
+            .map_or(Zone::Local, |spec| spec.zone);
+        let mut converter = ZoneConverter::new(default);
+        if let Some(file) = &self.host_timezones {
+            let text =
+                fs::read_to_string(file).map_err(|err| CliError::NoInput(file.clone(), err))?;
+            let by_host = timestamp::parse_host_zones(&text)
+                .map_err(|err| CliError::Config(file.clone(), err))?;
+            converter = converter.with_host_zones(by_host);
+        }
+        Ok(converter)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ZoneSpec {
+    path: Option<PathBuf>,
+    zone: Zone,
+}
+
+fn parse_zone_spec(arg: &str) -> Result<ZoneSpec, String> {
+    // Zone names never contain `=`, so the last one separates the path.
+    let (path, zone) = match arg.rsplit_once('=') {
+        Some((path, zone)) => (Some(PathBuf::from(path)), zone),
+        None => (None, arg),
+    };
+    Ok(ZoneSpec {
+        path,
+        zone: zone.parse()?,
+    })
+}
+
+#[derive(Debug, Args)]
+struct KeywordArgs {
+    /// Number of keywords and phrases to report; `--top` by default
+    #[arg(long, value_name = "N")]
+    top_terms: Option<usize>,
+    /// Skip keywords shorter than this many characters
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MIN_KEYWORD_LENGTH)]
+    min_keyword_length: usize,
+    /// Count numbers such as `11` or `10.105.160.95` as keywords
+    #[arg(long)]
+    keep_numbers: bool,
+    /// Count hex addresses and identifiers such as `0x0` or UUIDs as keywords
+    #[arg(long)]
+    keep_hex: bool,
+    /// Count keywords in the case they were written in instead of lowercase
+    #[arg(long)]
+    keep_case: bool,
+    /// Built-in stopwords: `english`, `log-noise` or `none`. May be
+    /// repeated; giving any replaces the default
+    #[arg(
+        long = "stopword-preset",
+        value_name = "PRESET",
+        default_value = DEFAULT_STOPWORD_PRESET,
+        value_parser = parse_preset
+    )]
+    stopword_presets: Vec<Option<Preset>>,
+    /// File of extra stopwords, separated by whitespace, with `#` comments.
+    /// May be repeated
+    #[arg(long = "stopwords-file", value_name = "FILE")]
+    stopword_files: Vec<PathBuf>,
+    /// Extra stopwords, comma-separated. May be repeated
+    #[arg(long = "stopword", value_name = "WORD", value_delimiter = ',')]
+    stopwords: Vec<String>,
+}
+
+impl KeywordArgs {
+    fn analyzer(&self) -> Result<Analyzer, CliError> {
+        let mut stopwords = self
+            .stopword_presets
+            .iter()
+            .flatten()
+            .fold(Stopwords::new(), |stopwords, &preset| {
+                stopwords.with_preset(preset)
+            })
+            .with_words(&self.stopwords);
+        for file in &self.stopword_files {
+            let text =
+                fs::read_to_string(file).map_err(|err| CliError::NoInput(file.clone(), err))?;
+            stopwords = stopwords.with_words(stopwords::parse_stopwords(&text));
+        }
+        let mut analyzer = Analyzer::new();
+        if let Some(top_terms) = self.top_terms {
+            analyzer = analyzer.with_top_terms(top_terms);
+        }
+        Ok(analyzer
+            .with_tokenizer(
+                Tokenizer::new()
+                    .with_min_length(self.min_keyword_length)
+                    .keep_numbers(self.keep_numbers)
+                    .keep_hex(self.keep_hex)
+                    .lowercase(!self.keep_case),
+            )
+            .with_stopwords(stopwords))
+    }
 }
 
-impl Summary {
-    fn new(
-        total_entries: usize,
-        by_process: Vec<(String, usize)>,
-        by_hostname: Vec<(String, usize)>,
-        most_frequent_process: String,
-        most_frequent_hostname: String,
-        top_keywords: Vec<String>,
-    ) -> Self {
+/// A preset name, or `none` for no built-in stopwords.
+fn parse_preset(arg: &str) -> Result<Option<Preset>, String> {
+    if arg == NO_STOPWORD_PRESET {
+        return Ok(None);
+    }
+    arg.parse().map(Some)
+}
+
+#[derive(Debug, Args)]
+struct FormatArgs {
+    /// Layout of the input lines: `bsd`, `rfc5424`, `unified`, `journal`, a
+    /// name from `--patterns`, or `auto` to pick, per file, the format that
+    /// parses most of its first lines
+    #[arg(long, value_name = "FORMAT", default_value = AUTO_FORMAT)]
+    input_format: String,
+    /// File of `<name> = <pattern>` lines defining extra formats, such as
+    /// `worker = %{DATE} %{TIME} [%{_LEVEL}] %{PROC} pid=%{PID} %{MSG}`
+    #[arg(long, value_name = "FILE")]
+    patterns: Option<PathBuf>,
+    /// Number of lines sampled to detect the format
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_SAMPLE_LINES)]
+    sample_lines: usize,
+    /// Fraction of sampled lines the detected format must parse
+    #[arg(long, value_name = "FRACTION", default_value_t = DEFAULT_MIN_MATCH)]
+    min_match: f64,
+}
+
+impl Default for FormatArgs {
+    fn default() -> Self {
         Self {
-            total_entries: total_entries,
-            by_process: by_process,
-            by_hostname: by_hostname,
-            most_frequent_process: most_frequent_process,
-            most_frequent_hostname: most_frequent_hostname,
-            top_keywords: top_keywords,
+            input_format: AUTO_FORMAT.to_string(),
+            patterns: None,
+            sample_lines: DEFAULT_SAMPLE_LINES,
+            min_match: DEFAULT_MIN_MATCH,
         }
     }
 }
 
-fn main() -> io::Result<()> {
-    let file_path = Path::new("./src/Mac_2k.log");
-    let file = File::open(file_path)?;
-    let reader = io::BufReader::new(file);
-    let lines = reader.lines();
-    let mut process_freq: HashMap<String, u32> = HashMap::new();
-    let mut host_name_freq: HashMap<String, u32> = HashMap::new();
-    let mut total_entries = 0;
-    let mut most_freq_keywords: HashMap<String, u32> = HashMap::new();
-    let stop_words: HashSet<String> = stopwords().iter().map(|x| x.to_string()).collect();
-
-    for line in lines {
-        let line = line?;
-        total_entries += 1;
-        if let Ok((_month_str, _day_str, _time_str, hostname_str, process_str, _pid_str, message_str)) =
-            parse_log_entry(line.as_str())
-        {
-            let process_score = process_freq.entry(process_str.to_string()).or_insert(0);
-            *process_score += 1;
-            let host_score = host_name_freq.entry(hostname_str.to_string()).or_insert(0);
-            *host_score += 1;
-            for keyword in message_str.split_whitespace() {
-                if !stop_words.contains(keyword) {
-                    let keyword_count =
-                        most_freq_keywords.entry(keyword.to_string()).or_insert(1);
-                    *keyword_count += 1;
+impl FormatArgs {
+    /// The built-in formats followed by those from `--patterns`.
+    fn formats(&self) -> Result<Vec<LogFormat>, CliError> {
+        let mut formats = LogFormat::BUILT_IN.to_vec();
+        if let Some(file) = &self.patterns {
+            let text =
+                fs::read_to_string(file).map_err(|err| CliError::NoInput(file.clone(), err))?;
+            let patterns = pattern::parse_patterns(&text)
+                .map_err(|err| CliError::Config(file.clone(), err))?;
+            for pattern in patterns {
+                if pattern.name() == AUTO_FORMAT
+                    || formats.iter().any(|format| format.name() == pattern.name())
+                {
+                    let err = format!("{} is the name of a built-in format", pattern.name());
+                    return Err(CliError::Config(file.clone(), err));
                 }
+                formats.push(LogFormat::Pattern(Arc::new(pattern)));
             }
         }
+        Ok(formats)
     }
-    let mut sorted_processes_freq = process_freq.iter().collect::<Vec<_>>();
-    sorted_processes_freq.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-    let top_processes = sorted_processes_freq.iter().take(3).collect::<Vec<_>>();
-
-    let mut sorted_host_name_freq = host_name_freq.iter().collect::<Vec<_>>();
-    sorted_host_name_freq.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-    let top_hosts = sorted_host_name_freq.iter().take(3).collect::<Vec<_>>();
-
-    let mut sorted_total_keyword_freq = most_freq_keywords.iter().collect::<Vec<_>>();
-    sorted_total_keyword_freq.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-    let top_keywords = sorted_total_keyword_freq.iter().take(3).collect::<Vec<_>>();
-
-    let summary = Summary::new(
-        total_entries,
-        top_processes.iter().map(|&&(name, &count)| (name.clone(), count as usize)).collect(),
-        top_hosts.iter().map(|&&(name, &count)| (name.clone(), count as usize)).collect(),
-        top_processes.first().map(|&&(name, _)| name.clone()).unwrap_or_default(),
-        top_hosts.first().map(|&&(name, _)| name.clone()).unwrap_or_default(),
-        top_keywords.iter().map(|&&(name, _)| massage_keyword(name)).collect(),
-    );
 
-    println!("{:?}", summary);
+    /// The format to read `path` with, sampling its first lines from
+    /// `reader` when detecting. Returns a reader positioned at the start.
+    fn detect(
+        &self,
+        path: &Path,
+        reader: Box<dyn BufRead>,
+    ) -> Result<(LogFormat, Box<dyn BufRead>), CliError> {
+        let formats = self.formats()?;
+        if self.input_format != AUTO_FORMAT {
+            let format = formats
+                .into_iter()
+                .find(|format| format.name() == self.input_format)
+                .ok_or_else(|| {
+                    CliError::Usage(format!("unknown input format: {}", self.input_format))
+                })?;
+            return Ok((format, reader));
+        }
+        let (sample, reader) = input::sample_lines(reader, self.sample_lines)
+            .map_err(|err| CliError::Io(path.to_path_buf(), err))?;
+        let format = self.choose(&formats, path, &sample)?;
+        Ok((format, reader))
+    }
 
-    Ok(())
+    /// Picks the best of `formats` for `sample`, failing when even that one
+    /// parses less than `--min-match` of it. An empty sample reads as BSD.
+    fn choose(
+        &self,
+        formats: &[LogFormat],
+        path: &Path,
+        sample: &[String],
+    ) -> Result<LogFormat, CliError> {
+        let Some(detection) = format::detect(formats, sample.iter().map(String::as_str)) else {
+            return Ok(LogFormat::default());
+        };
+        if detection.score() < self.min_match {
+            return Err(CliError::UnknownFormat(
+                path.to_path_buf(),
+                detection,
+                self.min_match,
+            ));
+        }
+        eprintln!(
+            "{}: detected {} format ({} of {} sampled lines parsed)",
+            path.display(),
+            detection.format,
+            detection.matched,
+            detection.sampled
+        );
+        Ok(detection.format)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ReportFormat {
+    Json,
+    Text,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ConvertFormat {
+    Ndjson,
+    Csv,
+}
+
+#[derive(Debug)]
+enum CliError {
+    /// An input could not be opened.
+    NoInput(PathBuf, io::Error),
+    /// Reading an input or writing the output failed.
+    Io(PathBuf, io::Error),
+    /// An argument was well-formed but named something that does not exist.
+    Usage(String),
+    /// A configuration file could not be understood.
+    Config(PathBuf, String),
+    /// No format parsed enough of an input's sample: the best guess and the
+    /// fraction required.
+    UnknownFormat(PathBuf, Detection, f64),
+}
+
+impl CliError {
+    fn exit_code(&self) -> u8 {
+        match self {
+            CliError::NoInput(..) => EXIT_NO_INPUT,
+            CliError::Io(..) => EXIT_IO_ERROR,
+            CliError::Usage(..) => EXIT_USAGE,
+            CliError::Config(..) => EXIT_CONFIG,
+            CliError::UnknownFormat(..) => EXIT_DATA_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::NoInput(path, err) => write!(f, "cannot open {}: {}", path.display(), err),
+            CliError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            CliError::Usage(err) => f.write_str(err),
+            CliError::Config(path, err) => write!(f, "{}: {}", path.display(), err),
+            CliError::UnknownFormat(path, best, min_match) => write!(
+                f,
+                "{}: unrecognised log format; best match {} parsed {} of {} sampled lines \
+                 ({:.0}%, need {:.0}%)",
+                path.display(),
+                best.format,
+                best.matched,
+                best.sampled,
+                best.score() * 100.0,
+                min_match * 100.0
+            ),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Summary(args) => run_summary(args),
+        Command::Grep(args) => run_grep(args),
+        Command::Stats(args) => run_stats(args),
+        Command::Convert(args) => run_convert(args),
+        Command::Follow(args) => run_follow(args),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        // The reader went away (`log_analyzer grep ... | head`); not an error.
+        Err(CliError::Io(_, err)) if err.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("log_analyzer: {}", err);
+            ExitCode::from(err.exit_code())
+        }
+    }
 }
 
-fn massage_keyword(keyword: &str) -> String {
-    keyword.to_lowercase().chars().filter(|c| *c != ':').collect()
+fn run_summary(args: SummaryArgs) -> Result<(), CliError> {
+    // Only the summary reports per-process keywords and templates, so only
+    // it pays for collecting them.
+    let analyzer = args
+        .keywords
+        .analyzer()?
+        .score_process_keywords(true)
+        .with_template_miner(TemplateMiner::new());
+    let summary = analyze(&args.input, analyzer)?.summary(args.top);
+    let mut output = Output::create(Some(&args.output))?;
+    output.write(|writer| match args.format {
+        ReportFormat::Json => summary.write_json(writer),
+        ReportFormat::Text => summary.write_text(writer),
+    })?;
+    output.finish()?;
+    if !is_stdout(&args.output) {
+        eprintln!("Wrote summary to {}", args.output.display());
+    }
+    Ok(())
 }
 
-fn parse_log_entry(
-    input_record: &str,
-) -> Result<(String, String, String, String, String, String, String), String> {
-    let mut parts = input_record.split_whitespace();
-    let month_str = parts
-        .next()
-        .ok_or("No month found".to_string())
-        .and_then(|m_str| parse_month(m_str))?;
-    let day_str = parts
-        .next()
-        .ok_or("No day found".to_string())
-        .and_then(|d_str| parse_day(d_str))?;
-    let time_str = parts
-        .next()
-        .ok_or("No time found".to_string())
-        .and_then(|t_str| parse_time(t_str))?;
-    let hostname_str = parts
-        .next()
-        .ok_or("No hostname found".to_string())
-        .and_then(|h_str| Ok(h_str.to_string()))?;
-    let (process_name_str, pid_str) = parts
-        .next()
-        .ok_or("No process found".to_string())
-        .and_then(|p| parse_process_name_and_pid(p))?;
-    let message_str = parts.collect::<Vec<_>>().join(" ");
-    Ok((
-        month_str,
-        day_str,
-        time_str,
-        hostname_str,
-        process_name_str,
-        pid_str,
-        message_str,
-    ))
+fn run_stats(args: StatsArgs) -> Result<(), CliError> {
+    let stats = analyze(&args.input, args.keywords.analyzer()?)?.stats(args.top);
+    let mut output = Output::create(args.output.as_deref())?;
+    output.write(|writer| match args.format {
+        ReportFormat::Json => stats.write_json(writer),
+        ReportFormat::Text => stats.write_text(writer),
+    })?;
+    output.finish()
 }
 
-fn parse_process_name_and_pid(process_name_str: &str) -> Result<(String, String), String> {
-    let parts = process_name_str.split('[').collect::<Vec<_>>();
-    if parts.len() == 2 {
-        Ok((
-            parts[0].into(),
-            parts[1]
-                .strip_suffix("]:")
-                .get_or_insert_default()
-                .to_string(),
-        ))
+fn run_grep(args: GrepArgs) -> Result<(), CliError> {
+    let pattern = if args.ignore_case {
+        args.pattern.to_lowercase()
     } else {
-        Err(format!("Invalid process string: {}", process_name_str))
+        args.pattern.clone()
+    };
+    let mut output = Output::create(args.output.as_deref())?;
+    for_each_line(&args.input, |_, line| {
+        let Ok(entry) = &line.entry else {
+            return Ok(());
+        };
+        let selected = args.process.as_ref().is_none_or(|p| *p == entry.process)
+            && args.host.as_ref().is_none_or(|h| *h == entry.hostname);
+        let matched = if args.ignore_case {
+            entry.message.to_lowercase().contains(&pattern)
+        } else {
+            entry.message.contains(&pattern)
+        };
+        if !(selected && matched) {
+            return Ok(());
+        }
+        output.write(|writer| {
+            for _ in 0..line.occurrences {
+                match args.format {
+                    ReportFormat::Json => convert::write_ndjson(&mut *writer, entry)?,
+                    ReportFormat::Text => writeln!(writer, "{}", line.text)?,
+                }
+            }
+            Ok(())
+        })
+    })?;
+    output.finish()
+}
+
+fn run_convert(args: ConvertArgs) -> Result<(), CliError> {
+    let mut output = Output::create(args.output.as_deref())?;
+    if args.format == ConvertFormat::Csv {
+        output.write(|writer| writeln!(writer, "{}", CSV_HEADER))?;
     }
+    for path in input_files(&args.input)? {
+        let mut rejections = Rejections::new(&path, io::stderr());
+        for_each_line_in(&path, &args.input, |line| match &line.entry {
+            Ok(entry) => output.write(|writer| {
+                for _ in 0..line.occurrences {
+                    match args.format {
+                        ConvertFormat::Ndjson => convert::write_ndjson(&mut *writer, entry)?,
+                        ConvertFormat::Csv => convert::write_csv(&mut *writer, entry)?,
+                    }
+                }
+                Ok(())
+            }),
+            Err(err) => {
+                rejections.report(err);
+                Ok(())
+            }
+        })?;
+        rejections.finish();
+    }
+    output.finish()
 }
 
-fn parse_time(timestamp_str: &str) -> Result<String, String> {
-    let parts = timestamp_str.split(':').collect::<Vec<_>>();
-    if parts.len() == 3 {
-        for part in parts.iter() {
-            if part.len() != 2 {
-                return Err(format!("Invalid time format: {}", timestamp_str));
+/// Runs until interrupted. A report is produced at start-up and then after
+/// every interval in which new lines arrived.
+fn run_follow(args: FollowArgs) -> Result<(), CliError> {
+    // Opened first, so input that cannot be followed is refused before its
+    // format is detected.
+    let follower = Follower::open(&args.path, args.from_start).map_err(|err| {
+        if err.kind() == io::ErrorKind::Unsupported {
+            CliError::Usage(format!("{}: {}", args.path.display(), err))
+        } else {
+            CliError::NoInput(args.path.clone(), err)
+        }
+    })?;
+    let reader =
+        input::open(&args.path).map_err(|err| CliError::NoInput(args.path.clone(), err))?;
+    let (format, _) = args.line_format.detect(&args.path, reader)?;
+    let mut follower = follower.with_parser(
+        LineParser::new()
+            .expand_repeats(!args.no_expand_repeats)
+            .join_continuations(!args.no_join_continuations)
+            .with_format(format.clone())
+            .with_years(year_resolver(args.year, &args.path))
+            .with_zones(args.zones.converter(&args.path)?),
+    );
+    let mut analyzer = args.keywords.analyzer()?;
+    analyzer.start_file(&args.path);
+    analyzer.set_format(&format);
+    let mut rejections = Rejections::new(&args.path, io::stderr());
+    let mut changed = true;
+    loop {
+        let lines = follower
+            .poll()
+            .map_err(|err| CliError::Io(args.path.clone(), err))?;
+        for line in lines {
+            tally(&mut analyzer, &mut rejections, &line);
+            changed = true;
+        }
+        if changed {
+            let stats = analyzer.stats(args.top);
+            let report = |writer: &mut dyn Write| match args.format {
+                ReportFormat::Json => stats
+                    .write_json(&mut *writer)
+                    .and_then(|()| writeln!(writer)),
+                ReportFormat::Text => stats
+                    .write_text(&mut *writer)
+                    .and_then(|()| writeln!(writer)),
+            };
+            match &args.output {
+                Some(path) if !is_stdout(path) => rewrite_output(path, report)?,
+                _ => {
+                    let mut output = Output::create(None)?;
+                    output.write(report)?;
+                    output.finish()?;
+                }
             }
-            if part.parse::<u32>().is_err() {
-                return Err(format!("Invalid time - not a number: {}", timestamp_str));
+            changed = false;
+        }
+        thread::sleep(Duration::from_secs(args.interval));
+    }
+}
+
+/// Replaces `path` with freshly written contents via a temporary file and a
+/// rename, so readers never see a half-written report.
+fn rewrite_output(
+    path: &Path,
+    f: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> Result<(), CliError> {
+    let mut temp_name = path.as_os_str().to_owned();
+    temp_name.push(".tmp");
+    let temp_path = PathBuf::from(temp_name);
+    let mut output = Output::create(Some(&temp_path))?;
+    output.write(f)?;
+    output.finish()?;
+    fs::rename(&temp_path, path).map_err(|err| CliError::Io(path.to_path_buf(), err))
+}
+
+fn analyze(inputs: &InputArgs, mut analyzer: Analyzer) -> Result<Analyzer, CliError> {
+    for path in input_files(inputs)? {
+        analyzer.start_file(&path);
+        let mut rejections = Rejections::new(&path, io::stderr());
+        let format = for_each_line_in(&path, inputs, |line| {
+            tally(&mut analyzer, &mut rejections, &line);
+            Ok(())
+        })?;
+        rejections.finish();
+        analyzer.set_format(&format);
+    }
+    Ok(analyzer)
+}
+
+fn year_resolver(year: Option<i32>, path: &Path) -> YearResolver {
+    match (year, input::modified_date(path)) {
+        (Some(year), _) => YearResolver::starting_in(year),
+        (None, Some(modified)) => YearResolver::ending_by(modified),
+        (None, None) => YearResolver::ending_today(),
+    }
+}
+
+/// Records one line, once per occurrence, or reports why it was rejected.
+fn tally(
+    analyzer: &mut Analyzer,
+    rejections: &mut Rejections<'_, impl Write>,
+    line: &ParsedLine<'_>,
+) {
+    analyzer.add_continuation_lines(line.continuations);
+    match &line.entry {
+        Ok(entry) => {
+            for _ in 0..line.occurrences {
+                analyzer.record(entry);
             }
         }
-        Ok(format!("{}:{}:{}", parts[0], parts[1], parts[2]))
-    } else {
-        Err(format!("Invalid time: {}", timestamp_str))
+        Err(err) => {
+            rejections.report(err);
+            analyzer.reject(err);
+        }
     }
 }
 
-fn parse_month(month_str: &str) -> Result<String, String> {
-    let set_of_months = HashSet::from([
-        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
-    ]);
-    if set_of_months.contains(month_str.to_lowercase().as_str()) {
-        Ok(month_str.into())
-    } else {
-        Err(format!("Invalid month: {}", month_str))
+/// Reports the rejected lines of one file: the first
+/// [`MAX_REPORTED_REJECTIONS`] one by one, then only how many more there were,
+/// so a file in the wrong format does not flood the terminal.
+struct Rejections<'p, W> {
+    path: &'p Path,
+    out: W,
+    count: usize,
+}
+
+impl<'p, W: Write> Rejections<'p, W> {
+    fn new(path: &'p Path, out: W) -> Self {
+        Rejections {
+            path,
+            out,
+            count: 0,
+        }
+    }
+
+    /// Messages to `out` are best effort, as with `eprintln!`, so write
+    /// errors are ignored.
+    fn report(&mut self, err: &ParseError) {
+        self.count += 1;
+        if self.count <= MAX_REPORTED_REJECTIONS {
+            let _ = writeln!(self.out, "{}: skipping {}", self.path.display(), err);
+        } else if self.count == MAX_REPORTED_REJECTIONS + 1 {
+            let _ = writeln!(
+                self.out,
+                "{}: more lines failed to parse; counting the rest",
+                self.path.display()
+            );
+        }
+    }
+
+    /// Reports how many rejections went unreported, if any.
+    fn finish(mut self) {
+        if self.count > MAX_REPORTED_REJECTIONS {
+            let _ = writeln!(
+                self.out,
+                "{}: skipped {} more lines that failed to parse",
+                self.path.display(),
+                self.count - MAX_REPORTED_REJECTIONS
+            );
+        }
     }
 }
 
-fn parse_day(day_str: &str) -> Result<String, String> {
-    let day = day_str
-        .parse::<u32>()
-        .map_err(|_| format!("Invalid day: {}", day_str))?;
-    if day > 0 && day <= 31 {
-        Ok(day.to_string())
-    } else {
-        Err(format!("Invalid day: {}", day_str))
+/// Expands globs and directories in the command-line inputs, keeping the
+/// order in which the arguments were given.
+fn input_files(inputs: &InputArgs) -> Result<Vec<PathBuf>, CliError> {
+    let mut files = Vec::new();
+    for arg in inputs.paths() {
+        files.extend(input::expand_path(&arg).map_err(|err| CliError::NoInput(arg.clone(), err))?);
     }
+    Ok(files)
 }
 
-fn stopwords() -> Vec<&'static str> {
-    vec![
-        "0",
-        "=",
-        "-",
-        "able",
-        "about",
-        "above",
-        "abroad",
-        "according",
-        "accordingly",
-        "across",
-        "actually",
-        "adj",
-        "after",
-        "afterwards",
-        "again",
-        "against",
-        "ago",
-        "ahead",
-        "ain't",
-        "all",
-        "allow",
-        "allows",
-        "almost",
-        "alone",
-        "along",
-        "alongside",
-        "already",
-        "also",
-        "although",
-        "always",
-        "am",
-        "amid",
-        "amidst",
-        "among",
-        "amongst",
-        "an",
-        "and",
-        "another",
-        "any",
-        "anybody",
-        "anyhow",
-        "anyone",
-        "anything",
-        "anyway",
-        "anyways",
-        "anywhere",
-        "apart",
-        "appear",
-        "appreciate",
-        "appropriate",
-        "are",
-        "aren't",
-        "around",
-        "as",
-        "a's",
-        "aside",
-        "ask",
-        "asking",
-        "associated",
-        "at",
-        "available",
-        "away",
-        "awfully",
-        "back",
-        "backward",
-        "backwards",
-        "be",
-        "became",
-        "because",
-        "become",
-        "becomes",
-        "becoming",
-        "been",
-        "before",
-        "beforehand",
-        "begin",
-        "behind",
-        "being",
-        "believe",
-        "below",
-        "beside",
-        "besides",
-        "best",
-        "better",
-        "between",
-        "beyond",
-        "both",
-        "brief",
-        "but",
-        "by",
-        "came",
-        "can",
-        "cannot",
-        "cant",
-        "can't",
-        "caption",
-        "cause",
-        "causes",
-        "certain",
-        "certainly",
-        "changes",
-        "clearly",
-        "c'mon",
-        "co",
-        "co.",
-        "com",
-        "come",
-        "comes",
-        "concerning",
-        "consequently",
-        "consider",
-        "considering",
-        "contain",
-        "containing",
-        "contains",
-        "corresponding",
-        "could",
-        "couldn't",
-        "course",
-        "c's",
-        "currently",
-        "dare",
-        "daren't",
-        "definitely",
-        "described",
-        "despite",
-        "did",
-        "didn't",
-        "different",
-        "directly",
-        "do",
-        "does",
-        "doesn't",
-        "doing",
-        "done",
-        "don't",
-        "down",
-        "downwards",
-        "during",
-        "each",
-        "edu",
-        "eg",
-        "eight",
-        "eighty",
-        "either",
-        "else",
-        "elsewhere",
-        "end",
-        "ending",
-        "enough",
-        "entirely",
-        "especially",
-        "et",
-        "etc",
-        "even",
-        "ever",
-        "evermore",
-        "every",
-        "everybody",
-        "everyone",
-        "everything",
-        "everywhere",
-        "ex",
-        "exactly",
-        "example",
-        "except",
-        "fairly",
-        "far",
-        "farther",
-        "few",
-        "fewer",
-        "fifth",
-        "first",
-        "five",
-        "followed",
-        "following",
-        "follows",
-        "for",
-        "forever",
-        "former",
-        "formerly",
-        "forth",
-        "forward",
-        "found",
-        "four",
-        "from",
-        "further",
-        "furthermore",
-        "get",
-        "gets",
-        "getting",
-        "given",
-        "gives",
-        "go",
-        "goes",
-        "going",
-        "gone",
-        "got",
-        "gotten",
-        "greetings",
-        "had",
-        "hadn't",
-        "half",
-        "happens",
-        "hardly",
-        "has",
-        "hasn't",
-        "have",
-        "haven't",
-        "having",
-        "he",
-        "he'd",
-        "he'll",
-        "hello",
-        "help",
-        "hence",
-        "her",
-        "here",
-        "hereafter",
-        "hereby",
-        "herein",
-        "here's",
-        "hereupon",
-        "hers",
-        "herself",
-        "he's",
-        "hi",
-        "him",
-        "himself",
-        "his",
-        "hither",
-        "hopefully",
-        "how",
-        "howbeit",
-        "however",
-        "hundred",
-        "i'd",
-        "ie",
-        "if",
-        "ignored",
-        "i'll",
-        "i'm",
-        "immediate",
-        "in",
-        "inasmuch",
-        "inc",
-        "inc.",
-        "indeed",
-        "indicate",
-        "indicated",
-        "indicates",
-        "inner",
-        "inside",
-        "insofar",
-        "instead",
-        "into",
-        "inward",
-        "is",
-        "isn't",
-        "it",
-        "it'd",
-        "it'll",
-        "its",
-        "it's",
-        "itself",
-        "i've",
-        "just",
-        "k",
-        "keep",
-        "keeps",
-        "kept",
-        "know",
-        "known",
-        "knows",
-        "last",
-        "lately",
-        "later",
-        "latter",
-        "latterly",
-        "least",
-        "less",
-        "lest",
-        "let",
-        "let's",
-        "like",
-        "liked",
-        "likely",
-        "likewise",
-        "little",
-        "look",
-        "looking",
-        "looks",
-        "low",
-        "lower",
-        "ltd",
-        "made",
-        "mainly",
-        "make",
-        "makes",
-        "many",
-        "may",
-        "maybe",
-        "mayn't",
-        "me",
-        "mean",
-        "meantime",
-        "meanwhile",
-        "merely",
-        "might",
-        "mightn't",
-        "mine",
-        "minus",
-        "miss",
-        "more",
-        "moreover",
-        "most",
-        "mostly",
-        "mr",
-        "mrs",
-        "much",
-        "must",
-        "mustn't",
-        "my",
-        "myself",
-        "name",
-        "namely",
-        "nd",
-        "near",
-        "nearly",
-        "necessary",
-        "need",
-        "needn't",
-        "needs",
-        "neither",
-        "never",
-        "neverf",
-        "neverless",
-        "nevertheless",
-        "new",
-        "next",
-        "nine",
-        "ninety",
-        "no",
-        "nobody",
-        "non",
-        "none",
-        "nonetheless",
-        "noone",
-        "no-one",
-        "nor",
-        "normally",
-        "not",
-        "nothing",
-        "notwithstanding",
-        "novel",
-        "now",
-        "nowhere",
-        "obviously",
-        "of",
-        "off",
-        "often",
-        "oh",
-        "ok",
-        "okay",
-        "old",
-        "on",
-        "once",
-        "one",
-        "ones",
-        "one's",
-        "only",
-        "onto",
-        "opposite",
-        "or",
-        "other",
-        "others",
-        "otherwise",
-        "ought",
-        "oughtn't",
-        "our",
-        "ours",
-        "ourselves",
-        "out",
-        "outside",
-        "over",
-        "overall",
-        "own",
-        "particular",
-        "particularly",
-        "past",
-        "per",
-        "perhaps",
-        "placed",
-        "please",
-        "plus",
-        "possible",
-        "presumably",
-        "probably",
-        "provided",
-        "provides",
-        "que",
-        "quite",
-        "qv",
-        "rather",
-        "rd",
-        "re",
-        "really",
-        "reasonably",
-        "recent",
-        "recently",
-        "regarding",
-        "regardless",
-        "regards",
-        "relatively",
-        "respectively",
-        "right",
-        "round",
-        "said",
-        "same",
-        "saw",
-        "say",
-        "saying",
-        "says",
-        "second",
-        "secondly",
-        "see",
-        "seeing",
-        "seem",
-        "seemed",
-        "seeming",
-        "seems",
-        "seen",
-        "self",
-        "selves",
-        "sensible",
-        "sent",
-        "serious",
-        "seriously",
-        "seven",
-        "several",
-        "shall",
-        "shan't",
-        "she",
-        "she'd",
-        "she'll",
-        "she's",
-        "should",
-        "shouldn't",
-        "since",
-        "six",
-        "so",
-        "some",
-        "somebody",
-        "someday",
-        "somehow",
-        "someone",
-        "something",
-        "sometime",
-        "sometimes",
-        "somewhat",
-        "somewhere",
-        "soon",
-        "sorry",
-        "specified",
-        "specify",
-        "specifying",
-        "still",
-        "sub",
-        "such",
-        "sup",
-        "sure",
-        "take",
-        "taken",
-        "taking",
-        "tell",
-        "tends",
-        "th",
-        "than",
-        "thank",
-        "thanks",
-        "thanx",
-        "that",
-        "that'll",
-        "thats",
-        "that's",
-        "that've",
-        "the",
-        "their",
-        "theirs",
-        "them",
-        "themselves",
-        "then",
-        "thence",
-        "there",
-        "thereafter",
-        "thereby",
-        "there'd",
-        "therefore",
-        "therein",
-        "there'll",
-        "there're",
-        "theres",
-        "there's",
-        "thereupon",
-        "there've",
-        "these",
-        "they",
-        "they'd",
-        "they'll",
-        "they're",
-        "they've",
-        "thing",
-        "things",
-        "think",
-        "third",
-        "thirty",
-        "this",
-        "thorough",
-        "thoroughly",
-        "those",
-        "though",
-        "three",
-        "through",
-        "throughout",
-        "thru",
-        "thus",
-        "till",
-        "to",
-        "together",
-        "too",
-        "took",
-        "toward",
-        "towards",
-        "tried",
-        "tries",
-        "truly",
-        "try",
-        "trying",
-        "t's",
-        "twice",
-        "two",
-        "un",
-        "under",
-        "underneath",
-        "undoing",
-        "unfortunately",
-        "unless",
-        "unlike",
-        "unlikely",
-        "until",
-        "unto",
-        "up",
-        "upon",
-        "upwards",
-        "us",
-        "use",
-        "used",
-        "useful",
-        "uses",
-        "using",
-        "usually",
-        "v",
-        "value",
-        "various",
-        "versus",
-        "very",
-        "via",
-        "viz",
-        "vs",
-        "want",
-        "wants",
-        "was",
-        "wasn't",
-        "way",
-        "we",
-        "we'd",
-        "welcome",
-        "well",
-        "we'll",
-        "went",
-        "were",
-        "we're",
-        "weren't",
-        "we've",
-        "what",
-        "whatever",
-        "what'll",
-        "what's",
-        "what've",
-        "when",
-        "whence",
-        "whenever",
-        "where",
-        "whereafter",
-        "whereas",
-        "whereby",
-        "wherein",
-        "where's",
-        "whereupon",
-        "wherever",
-        "whether",
-        "which",
-        "whichever",
-        "while",
-        "whilst",
-        "whither",
-        "who",
-        "who'd",
-        "whoever",
-        "whole",
-        "who'll",
-        "whom",
-        "whomever",
-        "who's",
-        "whose",
-        "why",
-        "will",
-        "willing",
-        "wish",
-        "with",
-        "within",
-        "without",
-        "wonder",
-        "won't",
-        "would",
-        "wouldn't",
-        "yes",
-        "yet",
-        "you",
-        "you'd",
-        "you'll",
-        "your",
-        "you're",
-        "yours",
-        "yourself",
-        "yourselves",
-        "you've",
-        "zero",
-        "a",
-        "how's",
-        "i",
-        "when's",
-        "why's",
-        "b",
-        "c",
-        "d",
-        "e",
-        "f",
-        "g",
-        "h",
-        "j",
-        "l",
-        "m",
-        "n",
-        "o",
-        "p",
-        "q",
-        "r",
-        "s",
-        "t",
-        "u",
-        "uucp",
-        "w",
-        "x",
-        "y",
-        "z",
-        "I",
-        "www",
-        "amount",
-        "bill",
-        "bottom",
-        "call",
-        "computer",
-        "con",
-        "couldnt",
-        "cry",
-        "de",
-        "describe",
-        "detail",
-        "due",
-        "eleven",
-        "empty",
-        "fifteen",
-        "fifty",
-        "fill",
-        "find",
-        "fire",
-        "forty",
-        "front",
-        "full",
-        "give",
-        "hasnt",
-        "herse",
-        "himse",
-        "interest",
-        "itse”",
-        "mill",
-        "move",
-        "myse”",
-        "part",
-        "put",
-        "show",
-        "side",
-        "sincere",
-        "sixty",
-        "system",
-        "ten",
-        "thick",
-        "thin",
-        "top",
-        "twelve",
-        "twenty",
-        "abst",
-        "accordance",
-        "act",
-        "added",
-        "adopted",
-        "affected",
-        "affecting",
-        "affects",
-        "ah",
-        "announce",
-        "anymore",
-        "apparently",
-        "approximately",
-        "aren",
-        "arent",
-        "arise",
-        "auth",
-        "beginning",
-        "beginnings",
-        "begins",
-        "biol",
-        "briefly",
-        "ca",
-        "date",
-        "ed",
-        "effect",
-        "et-al",
-        "ff",
-        "fix",
-        "gave",
-        "giving",
-        "heres",
-        "hes",
-        "hid",
-        "home",
-        "id",
-        "im",
-        "immediately",
-        "importance",
-        "important",
-        "index",
-        "information",
-        "invention",
-        "itd",
-        "keys",
-        "kg",
-        "km",
-        "largely",
-        "lets",
-        "line",
-        "'ll",
-        "means",
-        "mg",
-        "million",
-        "ml",
-        "mug",
-        "na",
-        "nay",
-        "necessarily",
-        "nos",
-        "noted",
-        "obtain",
-        "obtained",
-        "omitted",
-        "ord",
-        "owing",
-        "page",
-        "pages",
-        "poorly",
-        "possibly",
-        "potentially",
-        "pp",
-        "predominantly",
-        "present",
-        "previously",
-        "primarily",
-        "promptly",
-        "proud",
-        "quickly",
-        "ran",
-        "readily",
-        "ref",
-        "refs",
-        "related",
-        "research",
-        "resulted",
-        "resulting",
-        "results",
-        "run",
-        "sec",
-        "section",
-        "shed",
-        "shes",
-        "showed",
-        "shown",
-        "showns",
-        "shows",
-        "significant",
-        "significantly",
-        "similar",
-        "similarly",
-        "slightly",
-        "somethan",
-        "specifically",
-        "state",
-        "states",
-        "stop",
-        "strongly",
-        "substantially",
-        "successfully",
-        "sufficiently",
-        "suggest",
-        "thered",
-        "thereof",
-        "therere",
-        "thereto",
-        "theyd",
-        "theyre",
-        "thou",
-        "thoughh",
-        "thousand",
-        "throug",
-        "til",
-        "tip",
-        "ts",
-        "ups",
-        "usefully",
-        "usefulness",
-        "'ve",
-        "vol",
-        "vols",
-        "wed",
-        "whats",
-        "wheres",
-        "whim",
-        "whod",
-        "whos",
-        "widely",
-        "words",
-        "world",
-        "youd",
-        "youre",
-    ]
+/// Reads every input in order, handing each parsed line to `f` along with the
+/// file it came from.
+fn for_each_line(
+    inputs: &InputArgs,
+    mut f: impl FnMut(&Path, ParsedLine<'_>) -> Result<(), CliError>,
+) -> Result<(), CliError> {
+    for path in input_files(inputs)? {
+        for_each_line_in(&path, inputs, |line| f(&path, line))?;
+    }
+    Ok(())
+}
+
+/// Reads one file, numbering its lines from 1, and returns the format it
+/// was read as.
+fn for_each_line_in(
+    path: &Path,
+    inputs: &InputArgs,
+    mut f: impl FnMut(ParsedLine<'_>) -> Result<(), CliError>,
+) -> Result<LogFormat, CliError> {
+    let reader = input::open(path).map_err(|err| CliError::NoInput(path.to_path_buf(), err))?;
+    let (format, mut reader) = inputs.line_format.detect(path, reader)?;
+    let mut parser = inputs.line_parser(path, format.clone())?;
+    let mut buffer = Vec::new();
+    while let Some(text) = input::read_line(&mut reader, &mut buffer)
+        .map_err(|err| CliError::Io(path.to_path_buf(), err))?
+    {
+        if let Some(line) = parser.push(&text) {
+            f(line)?;
+        }
+    }
+    if let Some(line) = parser.finish() {
+        f(line)?;
+    }
+    Ok(format)
 }
 
+fn is_stdout(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// The file (or stdout, for no path or `-`) a command writes its results to.
+struct Output {
+    path: PathBuf,
+    writer: Box<dyn Write>,
+}
+
+impl Output {
+    fn create(path: Option<&Path>) -> Result<Self, CliError> {
+        let path = path.unwrap_or(Path::new("-")).to_path_buf();
+        let writer: Box<dyn Write> = if is_stdout(&path) {
+            Box::new(BufWriter::new(io::stdout().lock()))
+        } else {
+            let file = File::create(&path).map_err(|err| CliError::Io(path.clone(), err))?;
+            Box::new(BufWriter::new(file))
+        };
+        Ok(Self { path, writer })
+    }
+
+    fn write(&mut self, f: impl FnOnce(&mut dyn Write) -> io::Result<()>) -> Result<(), CliError> {
+        f(&mut self.writer).map_err(|err| CliError::Io(self.path.clone(), err))
+    }
+
+    fn finish(mut self) -> Result<(), CliError> {
+        self.writer
+            .flush()
+            .map_err(|err| CliError::Io(self.path.clone(), err))
+    }
+}
+
+// Shared with the library's tests.
+#[cfg(test)]
+mod scratch;
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::scratch::Scratch;
+    use log_analyzer::ParseErrorKind;
 
     #[test]
-    fn parse_a_valid_month() {
-        assert_eq!(parse_month("Jul").unwrap(), "Jul".to_string());
+    fn summary_output_defaults_to_summary_json() {
+        let cli = Cli::try_parse_from(["log_analyzer", "summary", "system.log"]).unwrap();
+        let Command::Summary(args) = cli.command else {
+            panic!("expected summary command");
+        };
+        assert_eq!(args.output, PathBuf::from("summary.json"));
+        assert_eq!(args.top, DEFAULT_TOP_N);
+        assert_eq!(args.format, ReportFormat::Json);
+        assert_eq!(args.input.inputs, vec![PathBuf::from("system.log")]);
     }
 
     #[test]
-    fn parse_an_invalid_month() {
-        assert!(parse_month("xxx").is_err());
+    fn output_path_from_flag() {
+        let cli = Cli::try_parse_from([
+            "log_analyzer",
+            "summary",
+            "--output",
+            "out/report.json",
+            "-n",
+            "10",
+            "system.log",
+        ])
+        .unwrap();
+        let Command::Summary(args) = cli.command else {
+            panic!("expected summary command");
+        };
+        assert_eq!(args.output, PathBuf::from("out/report.json"));
+        assert_eq!(args.top, 10);
+        assert!(Cli::try_parse_from(["log_analyzer", "summary", "system.log", "-o"]).is_err());
     }
 
     #[test]
-    fn parse_a_valid_day() {
-        assert_eq!(parse_day("1").unwrap(), "1".to_string());
+    fn no_inputs_means_stdin() {
+        let cli = Cli::try_parse_from(["log_analyzer", "stats"]).unwrap();
+        let Command::Stats(args) = cli.command else {
+            panic!("expected stats command");
+        };
+        assert_eq!(args.input.paths(), vec![PathBuf::from("-")]);
     }
 
     #[test]
-    fn parse_an_invalid_day() {
-        assert!(parse_day("0").is_err());
+    fn stopword_presets_replace_the_default() {
+        let cli = Cli::try_parse_from(["log_analyzer", "stats"]).unwrap();
+        let Command::Stats(args) = cli.command else {
+            panic!("expected stats command");
+        };
+        assert_eq!(args.keywords.stopword_presets, vec![Some(Preset::English)]);
+
+        let cli = Cli::try_parse_from([
+            "log_analyzer",
+            "stats",
+            "--stopword-preset",
+            "none",
+            "--stopword-preset",
+            "log-noise",
+            "--stopword",
+            "arpt,wl0",
+        ])
+        .unwrap();
+        let Command::Stats(args) = cli.command else {
+            panic!("expected stats command");
+        };
+        assert_eq!(
+            args.keywords.stopword_presets,
+            vec![None, Some(Preset::LogNoise)]
+        );
+        assert_eq!(args.keywords.stopwords, ["arpt", "wl0"]);
+        assert!(
+            Cli::try_parse_from(["log_analyzer", "stats", "--stopword-preset", "french"]).is_err()
+        );
+    }
+
+    #[test]
+    fn missing_input_maps_to_no_input_exit_code() {
+        let inputs = InputArgs {
+            inputs: vec![PathBuf::from("does/not/exist.log")],
+            no_expand_repeats: false,
+            no_join_continuations: false,
+            year: None,
+            zones: ZoneArgs::default(),
+            line_format: FormatArgs::default(),
+        };
+        let err = analyze(&inputs, Analyzer::new()).err().unwrap();
+        assert_eq!(err.exit_code(), EXIT_NO_INPUT);
     }
 
     #[test]
-    fn parse_a_valid_process_name() {
+    fn timezone_for_a_path_beats_the_default() {
+        let cli = Cli::try_parse_from([
+            "log_analyzer",
+            "stats",
+            "--timezone",
+            "Europe/Berlin",
+            "--timezone",
+            "logs/laptop=America/Los_Angeles",
+        ])
+        .unwrap();
+        let Command::Stats(args) = cli.command else {
+            panic!("expected stats command");
+        };
+        let zones = &args.input.zones;
         assert_eq!(
-            parse_process_name_and_pid("com.apple.CDScheduler[43]:").unwrap(),
-            ("com.apple.CDScheduler".to_string(), "43".to_string())
+            zones
+                .converter(Path::new("logs/laptop/system.log"))
+                .unwrap()
+                .zone_for("host"),
+            "America/Los_Angeles".parse().unwrap()
+        );
+        assert_eq!(
+            zones
+                .converter(Path::new("logs/server/system.log"))
+                .unwrap()
+                .zone_for("host"),
+            "Europe/Berlin".parse().unwrap()
+        );
+        assert!(
+            Cli::try_parse_from(["log_analyzer", "stats", "--timezone", "Mars/Olympus_Mons"])
+                .is_err()
         );
     }
 
     #[test]
-    fn parse_a_valid_line() {
-        let line = "Jul  1 09:01:05 calvisitor-10-105-160-95 com.apple.CDScheduler[43]: Thermal pressure state: 1 Memory pressure state: 0";
-        let (month, day, time, hostname, process, pid, message) = parse_log_entry(line).unwrap();
-        assert_eq!(month, "Jul");
-        assert_eq!(day, "1");
-        assert_eq!(time, "09:01:05");
-        assert_eq!(hostname, "calvisitor-10-105-160-95");
-        assert_eq!(process, "com.apple.CDScheduler");
-        assert_eq!(pid, "43");
+    fn undetectable_format_maps_to_data_error_exit_code() {
+        let format = FormatArgs::default();
+        let sample = [
+            "garbage",
+            "more garbage",
+            "Jul  1 09:00:55 host kernel[0]: fine",
+        ]
+        .map(String::from);
+        let err = format
+            .choose(&LogFormat::BUILT_IN, Path::new("mixed.log"), &sample)
+            .unwrap_err();
+        assert_eq!(err.exit_code(), EXIT_DATA_ERROR);
         assert_eq!(
-            message,
-            "Thermal pressure state: 1 Memory pressure state: 0"
+            err.to_string(),
+            "mixed.log: unrecognised log format; best match bsd parsed 1 of 3 sampled lines (33%, need 50%)"
+        );
+        assert_eq!(
+            format
+                .choose(&LogFormat::BUILT_IN, Path::new("empty.log"), &[])
+                .unwrap(),
+            LogFormat::Bsd
+        );
+        let format = FormatArgs {
+            min_match: 0.3,
+            ..FormatArgs::default()
+        };
+        assert_eq!(
+            format
+                .choose(&LogFormat::BUILT_IN, Path::new("mixed.log"), &sample)
+                .unwrap(),
+            LogFormat::Bsd
         );
     }
 
     #[test]
-    fn parse_another_valid_line() {
-        let line = "Jul  8 06:11:46 calvisitor-10-105-162-124 WindowServer[184]: send_datagram_available_ping: pid 445 failed to act on a ping it dequeued before timing out.
-";
-        let (month, day, time, hostname, process, pid, message) = parse_log_entry(line).unwrap();
-        assert_eq!(month, "Jul");
-        assert_eq!(day, "8");
-        assert_eq!(time, "06:11:46");
-        assert_eq!(hostname, "calvisitor-10-105-162-124");
-        assert_eq!(process, "WindowServer");
-        assert_eq!(pid, "184");
-        assert_eq!(
-            message,
-            "send_datagram_available_ping: pid 445 failed to act on a ping it dequeued before timing out."
+    fn input_format_names_patterns_from_the_patterns_file() {
+        let path = Scratch::file(
+            "patterns.conf",
+            "worker = %{DATE} %{TIME} [%{_LEVEL}] %{PROC} pid=%{PID} %{MSG}\n",
         );
+        let mut format = FormatArgs {
+            input_format: "worker".to_string(),
+            patterns: Some(path.to_path_buf()),
+            ..FormatArgs::default()
+        };
+        let reader: Box<dyn BufRead> = Box::new(&b""[..]);
+        let (detected, _) = format.detect(Path::new("w.log"), reader).unwrap();
+        assert_eq!(detected.name(), "worker");
+
+        format.input_format = "nginx".to_string();
+        let reader: Box<dyn BufRead> = Box::new(&b""[..]);
+        let err = format.detect(Path::new("w.log"), reader).err().unwrap();
+        assert_eq!(err.exit_code(), EXIT_USAGE);
+
+        fs::write(&path, "bsd = %{DATE} %{TIME} %{MSG}\n").unwrap();
+        let reader: Box<dyn BufRead> = Box::new(&b""[..]);
+        let err = format.detect(Path::new("w.log"), reader).err().unwrap();
+        assert_eq!(err.exit_code(), EXIT_CONFIG);
+    }
+
+    #[test]
+    fn malformed_host_timezones_maps_to_config_exit_code() {
+        let path = Scratch::file("host-zones.txt", "buildbox-01\n");
+        let zones = ZoneArgs {
+            timezones: Vec::new(),
+            host_timezones: Some(path.to_path_buf()),
+        };
+        let err = zones.converter(Path::new("system.log")).unwrap_err();
+        assert_eq!(err.exit_code(), EXIT_CONFIG);
     }
 
     #[test]
-    fn test_split_into_flds_str() {
-        let input_record = "Jul  1 09:01:05 calvisitor-10-105-160-95 com.apple.CDScheduler[43]: Thermal pressure state: 1 Memory pressure state: 0";
-        let (month_str, day_str, time_str, hostname_str, process_str, pid_str, message_str) =
-            parse_log_entry(input_record).unwrap();
-        assert_eq!(month_str, "Jul");
-        assert_eq!(day_str, "1");
-        assert_eq!(time_str, "09:01:05");
-        assert_eq!(hostname_str, "calvisitor-10-105-160-95");
-        assert_eq!(process_str, "com.apple.CDScheduler");
-        assert_eq!(pid_str, "43");
+    fn follow_interval_must_be_positive() {
+        let cli = Cli::try_parse_from(["log_analyzer", "follow", "system.log"]).unwrap();
+        let Command::Follow(args) = cli.command else {
+            panic!("expected follow command");
+        };
+        assert_eq!(args.interval, DEFAULT_FOLLOW_INTERVAL);
+        assert!(Cli::try_parse_from(["log_analyzer", "follow", "-i", "0", "system.log"]).is_err());
+    }
+
+    #[test]
+    fn only_the_first_rejections_of_a_file_are_reported() {
+        let mut out = Vec::new();
+        let mut rejections = Rejections::new(Path::new("system.log"), &mut out);
+        for line_number in 1..=MAX_REPORTED_REJECTIONS + 5 {
+            let err =
+                ParseError::new(ParseErrorKind::MissingHostname, 16).with_line_number(line_number);
+            rejections.report(&err);
+        }
+        rejections.finish();
+        let out = String::from_utf8(out).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), MAX_REPORTED_REJECTIONS + 2);
         assert_eq!(
-            message_str,
-            "Thermal pressure state: 1 Memory pressure state: 0"
+            lines[0],
+            "system.log: skipping line 1, byte 16: no hostname found"
         );
+        assert_eq!(
+            lines[MAX_REPORTED_REJECTIONS],
+            "system.log: more lines failed to parse; counting the rest"
+        );
+        assert_eq!(
+            lines[MAX_REPORTED_REJECTIONS + 1],
+            "system.log: skipped 5 more lines that failed to parse"
+        );
+
+        let mut out = Vec::new();
+        let mut rejections = Rejections::new(Path::new("system.log"), &mut out);
+        rejections.report(&ParseError::new(ParseErrorKind::MissingHostname, 16));
+        rejections.finish();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 1);
     }
 }
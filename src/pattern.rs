@@ -0,0 +1,540 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc};
+
+use crate::entry::{LogEntry, Month, TimeOfDay};
+use crate::error::{ParseError, ParseErrorKind};
+
+/// A user-defined line format: literal text with `%{FIELD}` captures.
+///
+/// ```text
+/// %{DATE} %{TIME} [%{_LEVEL}] %{PROC} pid=%{PID} %{MSG}
+/// ```
+///
+/// Fields map onto [`LogEntry`]:
+///
+/// | Capture        | Matches                                   | Sets              |
+/// |----------------|-------------------------------------------|-------------------|
+/// | `%{MONTH}`     | `Jul`                                     | month             |
+/// | `%{DAY}`       | `1` to `31`                               | day               |
+/// | `%{YEAR}`      | `2024`                                    | timestamp's year  |
+/// | `%{DATE}`      | `2024-07-01`                              | month, day, year  |
+/// | `%{TIME}`      | `09:00:55`, `09:00:55.123`                | time              |
+/// | `%{TIMESTAMP}` | `2024-07-01T09:00:55.123Z`, with or without an offset | all of the above |
+/// | `%{HOST}`      | anything                                  | hostname          |
+/// | `%{PROC}`      | anything                                  | process           |
+/// | `%{PID}`       | a number                                  | pid               |
+/// | `%{MSG}`       | anything                                  | message           |
+/// | `%{_NAME}`     | anything                                  | nothing           |
+///
+/// A capture runs up to the first place the literal text after it matches
+/// (to the end of the line for the last one), and every capture but the last
+/// must match at least one character. A run of whitespace in the pattern
+/// matches any run of whitespace in the line. A pattern needs a date (`DATE`,
+/// `TIMESTAMP`, or `MONTH` and `DAY`) and a time (`TIME` or `TIMESTAMP`);
+/// without `HOST` or `PROC` they are `-`, and without `PID` it is 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    name: String,
+    source: String,
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Item {
+    Literal(Vec<Piece>),
+    Capture(Field),
+}
+
+/// Part of a literal: exact text, or a run of whitespace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    Space,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Field {
+    Month,
+    Day,
+    Year,
+    Date,
+    Time,
+    Timestamp,
+    Host,
+    Proc,
+    Pid,
+    Msg,
+    Ignored,
+}
+
+impl FromStr for Field {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Ok(match name {
+            "MONTH" => Field::Month,
+            "DAY" => Field::Day,
+            "YEAR" => Field::Year,
+            "DATE" => Field::Date,
+            "TIME" => Field::Time,
+            "TIMESTAMP" => Field::Timestamp,
+            "HOST" => Field::Host,
+            "PROC" => Field::Proc,
+            "PID" => Field::Pid,
+            "MSG" => Field::Msg,
+            _ if name.starts_with('_') => Field::Ignored,
+            _ => return Err(format!("unknown capture %{{{}}}", name)),
+        })
+    }
+}
+
+impl Pattern {
+    /// Compiles `source`, checking that its captures are known, not
+    /// repeated, not adjacent, and together give a date and a time.
+    pub fn new(name: &str, source: &str) -> Result<Self, String> {
+        let mut items = Vec::new();
+        let mut seen = HashSet::new();
+        let mut literal = String::new();
+        let mut rest = source;
+        while let Some(start) = rest.find("%{") {
+            literal.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed %{{ in {:?}", source))?;
+            let capture = &rest[start + 2..start + end];
+            let field = capture.parse::<Field>()?;
+            if field != Field::Ignored && !seen.insert(field) {
+                return Err(format!("%{{{}}} appears twice", capture));
+            }
+            if !literal.is_empty() {
+                items.push(Item::Literal(pieces(&std::mem::take(&mut literal))));
+            } else if matches!(items.last(), Some(Item::Capture(_))) {
+                return Err("captures must be separated by literal text".to_string());
+            }
+            items.push(Item::Capture(field));
+            rest = &rest[start + end + 1..];
+        }
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            items.push(Item::Literal(pieces(&literal)));
+        }
+
+        let has = |field| seen.contains(&field);
+        if !(has(Field::Date) || has(Field::Timestamp) || has(Field::Month) && has(Field::Day)) {
+            return Err("needs %{DATE}, %{TIMESTAMP}, or %{MONTH} and %{DAY}".to_string());
+        }
+        if !(has(Field::Time) || has(Field::Timestamp)) {
+            return Err("needs %{TIME} or %{TIMESTAMP}".to_string());
+        }
+        Ok(Self {
+            name: name.to_string(),
+            source: source.to_string(),
+            items,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn parse<'a>(&self, line: &'a str) -> Result<LogEntry<'a>, ParseError> {
+        let mut fields = Captured::default();
+        let mut position = 0;
+        let mut items = self.items.iter().peekable();
+        while let Some(item) = items.next() {
+            match item {
+                Item::Literal(pieces) => {
+                    position = match_at(pieces, line, position)
+                        .ok_or_else(|| mismatch(pieces, position))?;
+                }
+                Item::Capture(field) => {
+                    let (end, next) = match items.peek() {
+                        Some(Item::Literal(pieces)) => {
+                            let found = find_literal(pieces, line, position)
+                                .ok_or_else(|| mismatch(pieces, position))?;
+                            items.next();
+                            found
+                        }
+                        _ => (line.len(), line.len()),
+                    };
+                    fields
+                        .set(*field, &line[position..end])
+                        .map_err(|kind| ParseError::new(kind, position))?;
+                    position = next;
+                }
+            }
+        }
+        if !line[position..].trim().is_empty() {
+            return Err(ParseError::new(
+                ParseErrorKind::PatternMismatch("end of line".to_string()),
+                position,
+            ));
+        }
+        fields.into_entry().map_err(|kind| ParseError::new(kind, 0))
+    }
+
+    /// Whether `line` matches the pattern up to and including its first
+    /// capture, which tells a new entry from a continuation line.
+    pub fn starts_entry(&self, line: &str) -> bool {
+        let mut fields = Captured::default();
+        let mut position = 0;
+        let mut items = self.items.iter().peekable();
+        while let Some(item) = items.next() {
+            match item {
+                Item::Literal(pieces) => match match_at(pieces, line, position) {
+                    Some(end) => position = end,
+                    None => return false,
+                },
+                Item::Capture(field) => {
+                    let end = match items.peek() {
+                        Some(Item::Literal(pieces)) => {
+                            find_literal(pieces, line, position).map(|(end, _)| end)
+                        }
+                        _ => Some(line.len()),
+                    };
+                    return end.is_some_and(|end| fields.set(*field, &line[position..end]).is_ok());
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Parses a pattern file: one `<name> = <pattern>` per line, with blank
+/// lines and lines starting with `#` ignored.
+///
+/// ```text
+/// # In-house daemons
+/// worker = %{DATE} %{TIME} [%{_LEVEL}] %{PROC} pid=%{PID} %{MSG}
+/// ```
+pub fn parse_patterns(text: &str) -> Result<Vec<Pattern>, String> {
+    let mut patterns: Vec<Pattern> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: String| format!("line {}: {}", index + 1, message);
+        let (name, source) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected <name> = <pattern>".to_string()))?;
+        let name = name.trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(error(format!("invalid name {:?}", name)));
+        }
+        if patterns.iter().any(|pattern| pattern.name == name) {
+            return Err(error(format!("{} is defined twice", name)));
+        }
+        patterns.push(Pattern::new(name, source.trim()).map_err(error)?);
+    }
+    Ok(patterns)
+}
+
+fn pieces(literal: &str) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    for c in literal.chars() {
+        if c.is_whitespace() {
+            if !text.is_empty() {
+                pieces.push(Piece::Text(std::mem::take(&mut text)));
+            }
+            if pieces.last() != Some(&Piece::Space) {
+                pieces.push(Piece::Space);
+            }
+        } else {
+            text.push(c);
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    pieces
+}
+
+/// Matches `pieces` at `start` and returns where the match ends.
+fn match_at(pieces: &[Piece], line: &str, start: usize) -> Option<usize> {
+    let mut position = start;
+    for piece in pieces {
+        match piece {
+            Piece::Text(text) => {
+                if !line[position..].starts_with(text.as_str()) {
+                    return None;
+                }
+                position += text.len();
+            }
+            Piece::Space => {
+                let rest = &line[position..];
+                let skipped = rest.len() - rest.trim_start().len();
+                if skipped == 0 {
+                    return None;
+                }
+                position += skipped;
+            }
+        }
+    }
+    Some(position)
+}
+
+/// First match of `pieces` after at least one character from `start`, as
+/// the match's start and end.
+fn find_literal(pieces: &[Piece], line: &str, start: usize) -> Option<(usize, usize)> {
+    line[start..]
+        .char_indices()
+        .skip(1)
+        .map(|(index, _)| start + index)
+        .find_map(|at| Some((at, match_at(pieces, line, at)?)))
+}
+
+fn mismatch(pieces: &[Piece], position: usize) -> ParseError {
+    let expected = pieces
+        .iter()
+        .map(|piece| match piece {
+            Piece::Text(text) => text.as_str(),
+            Piece::Space => " ",
+        })
+        .collect();
+    ParseError::new(ParseErrorKind::PatternMismatch(expected), position)
+}
+
+/// Values captured from one line, checked as they are set.
+#[derive(Default)]
+struct Captured<'a> {
+    month: Option<Month>,
+    day: Option<u8>,
+    year: Option<i32>,
+    date: Option<NaiveDate>,
+    time: Option<NaiveTime>,
+    timestamp: Option<(NaiveDateTime, Option<DateTime<Utc>>)>,
+    host: Option<&'a str>,
+    process: Option<&'a str>,
+    pid: Option<u32>,
+    message: Option<&'a str>,
+}
+
+impl<'a> Captured<'a> {
+    fn set(&mut self, field: Field, value: &'a str) -> Result<(), ParseErrorKind> {
+        let invalid_timestamp = || ParseErrorKind::InvalidTimestamp(value.to_string());
+        match field {
+            Field::Month => self.month = Some(value.parse()?),
+            Field::Day => {
+                let invalid = || ParseErrorKind::InvalidDay(value.to_string());
+                let day = value.parse::<u8>().map_err(|_| invalid())?;
+                if !(1..=31).contains(&day) {
+                    return Err(invalid());
+                }
+                self.day = Some(day);
+            }
+            Field::Year => {
+                if value.len() != 4 {
+                    return Err(invalid_timestamp());
+                }
+                self.year = Some(value.parse().map_err(|_| invalid_timestamp())?);
+            }
+            Field::Date => {
+                self.date = Some(
+                    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                        .map_err(|_| invalid_timestamp())?,
+                );
+            }
+            Field::Time => {
+                self.time = Some(
+                    NaiveTime::parse_from_str(value, "%H:%M:%S%.f")
+                        .map_err(|_| ParseErrorKind::InvalidTime(value.to_string()))?,
+                );
+            }
+            Field::Timestamp => {
+                self.timestamp = Some(match DateTime::parse_from_rfc3339(value) {
+                    Ok(timestamp) => (timestamp.naive_local(), Some(timestamp.with_timezone(&Utc))),
+                    Err(_) => ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+                        .into_iter()
+                        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+                        .map(|local| (local, None))
+                        .ok_or_else(invalid_timestamp)?,
+                });
+            }
+            Field::Host => self.host = Some(value),
+            Field::Proc => self.process = Some(value),
+            Field::Pid => {
+                self.pid = Some(
+                    value
+                        .parse()
+                        .map_err(|_| ParseErrorKind::InvalidPid(value.to_string()))?,
+                );
+            }
+            Field::Msg => self.message = Some(value),
+            Field::Ignored => {}
+        }
+        Ok(())
+    }
+
+    fn into_entry(self) -> Result<LogEntry<'a>, ParseErrorKind> {
+        let (timestamp, timestamp_utc) = match self.timestamp {
+            Some((local, utc)) => (Some(local), utc),
+            None => {
+                let date = match (self.date, self.year, self.month, self.day) {
+                    (Some(date), ..) => Some(date),
+                    (None, Some(year), Some(month), Some(day)) => Some(
+                        NaiveDate::from_ymd_opt(year, month.number(), day.into()).ok_or_else(
+                            || ParseErrorKind::InvalidDay(format!("{} {} {}", year, month, day)),
+                        )?,
+                    ),
+                    _ => None,
+                };
+                (date.zip(self.time).map(|(d, t)| d.and_time(t)), None)
+            }
+        };
+        let (month, day, time) = match timestamp {
+            Some(timestamp) => (
+                Month::ALL[timestamp.month0() as usize],
+                timestamp.day() as u8,
+                timestamp.time(),
+            ),
+            // Pattern::new guarantees MONTH, DAY and TIME when there is no
+            // full date.
+            None => (
+                self.month.unwrap_or(Month::Jan),
+                self.day.unwrap_or(1),
+                self.time.unwrap_or_default(),
+            ),
+        };
+        Ok(LogEntry {
+            month,
+            day,
+            time: TimeOfDay {
+                hour: time.hour() as u8,
+                minute: time.minute() as u8,
+                second: time.second() as u8,
+            },
+            hostname: Cow::Borrowed(self.host.unwrap_or("-")),
+            process: Cow::Borrowed(self.process.unwrap_or("-")),
+            pid: self.pid.unwrap_or(0),
+            message: Cow::Borrowed(self.message.unwrap_or_default()),
+            timestamp,
+            timestamp_utc,
+            priority: None,
+            rfc5424: None,
+            unified: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORKER: &str = "%{DATE} %{TIME} [%{_LEVEL}] %{PROC} pid=%{PID} %{MSG}";
+
+    #[test]
+    fn parse_in_house_daemon_lines() {
+        let pattern = Pattern::new("worker", WORKER).unwrap();
+        let entry = pattern
+            .parse("2024-07-01 09:00:55.123 [INFO] worker-3 pid=42 job  finished")
+            .unwrap();
+        assert_eq!(entry.month, Month::Jul);
+        assert_eq!(entry.day, 1);
+        assert_eq!(entry.time.to_string(), "09:00:55");
+        assert_eq!(
+            entry.timestamp.unwrap().to_string(),
+            "2024-07-01 09:00:55.123"
+        );
+        assert_eq!(entry.timestamp_utc, None);
+        assert_eq!(entry.hostname, "-");
+        assert_eq!(entry.process, "worker-3");
+        assert_eq!(entry.pid, 42);
+        assert_eq!(entry.message, "job  finished");
+    }
+
+    #[test]
+    fn describe_the_bsd_layout() {
+        let pattern = Pattern::new(
+            "bsd",
+            "%{MONTH} %{DAY} %{TIME} %{HOST} %{PROC}[%{PID}]: %{MSG}",
+        )
+        .unwrap();
+        let line = "Jul  4 23:22:09 calvisitor-10-105-160-95 Microsoft Word[14463]: .sdef warning";
+        let entry = pattern.parse(line).unwrap();
+        let bsd = crate::entry::parse_log_entry(line).unwrap();
+        assert_eq!(entry, bsd);
+        assert_eq!(entry.timestamp, None);
+    }
+
+    #[test]
+    fn entries_start_with_the_first_capture() {
+        let pattern = Pattern::new("worker", WORKER).unwrap();
+        assert!(pattern.starts_entry("2024-07-01 09:00:55.123 [INFO] worker-3"));
+        assert!(!pattern.starts_entry("Traceback (most recent call last):"));
+        assert!(!pattern.starts_entry("2024-07-01"));
+    }
+
+    #[test]
+    fn timestamp_capture_fills_in_utc() {
+        let pattern = Pattern::new("iso", "%{TIMESTAMP} %{HOST}: %{MSG}").unwrap();
+        let entry = pattern
+            .parse("2024-07-01T09:00:55+02:00 buildbox-01: disk full")
+            .unwrap();
+        assert_eq!(entry.hostname, "buildbox-01");
+        assert_eq!(
+            entry.timestamp_utc.unwrap().to_rfc3339(),
+            "2024-07-01T07:00:55+00:00"
+        );
+        assert_eq!(entry.process, "-");
+    }
+
+    #[test]
+    fn mismatched_lines_are_rejected() {
+        let pattern = Pattern::new("worker", WORKER).unwrap();
+        let err = pattern
+            .parse("2024-07-01 09:00:55 INFO worker-3 pid=42 x")
+            .unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::PatternMismatch(" [".to_string()));
+        assert_eq!(err.offset, 11);
+        let err = pattern
+            .parse("2024-07-01 09:00:55 [INFO] worker-3 pid=x42 x")
+            .unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidPid("x42".to_string()));
+        let err = pattern
+            .parse("2024-13-01 09:00:55 [INFO] worker-3 pid=42 x")
+            .unwrap_err();
+        assert_eq!(err.kind.name(), "invalid_timestamp");
+        let strict = Pattern::new("strict", "%{DATE} %{TIME} done").unwrap();
+        assert!(strict.parse("2024-07-01 09:00:55 done and more").is_err());
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        for source in [
+            "%{MONTH} %{DAY} %{HOST}",
+            "%{TIME} %{HOST}",
+            "%{DATE} %{TIME} %{HOST}%{PROC}",
+            "%{DATE} %{TIME} %{LEVEL}",
+            "%{DATE} %{TIME} %{HOST} %{HOST}",
+            "%{DATE} %{TIME",
+        ] {
+            assert!(Pattern::new("bad", source).is_err(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn parse_pattern_file() {
+        let patterns = parse_patterns(&format!(
+            "# In-house daemons\n\nworker = {}\nsyslog=%{{MONTH}} %{{DAY}} %{{TIME}} %{{MSG}}\n",
+            WORKER
+        ))
+        .unwrap();
+        assert_eq!(patterns.len(), 2);
+        assert_eq!(patterns[0].name(), "worker");
+        assert_eq!(patterns[0].source(), WORKER);
+        assert_eq!(patterns[1].name(), "syslog");
+        assert!(parse_patterns("worker").is_err());
+        assert!(parse_patterns(&format!("a = {}\na = {}", WORKER, WORKER)).is_err());
+        let err = parse_patterns("\nworker = %{DATE}").unwrap_err();
+        assert!(err.starts_with("line 2: "), "{}", err);
+    }
+}
@@ -0,0 +1,241 @@
+use std::fmt;
+
+use serde::{Serialize, Serializer};
+
+use crate::error::ParseErrorKind;
+
+/// The `<PRI>` value at the start of a syslog line: `facility * 8 + severity`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct Priority {
+    pub facility: Facility,
+    pub severity: Severity,
+}
+
+impl Priority {
+    pub fn from_value(value: u8) -> Option<Self> {
+        Some(Self {
+            facility: *Facility::ALL.get(usize::from(value / 8))?,
+            severity: Severity::ALL[usize::from(value % 8)],
+        })
+    }
+
+    pub fn value(self) -> u8 {
+        self.facility as u8 * 8 + self.severity as u8
+    }
+}
+
+/// Splits a leading `<PRI>` off `line`, returning the priority and the rest
+/// of the line. `None` when the line does not start with `<`; an error when
+/// it does but the value is not 0–191 written without leading zeros.
+pub fn strip_priority(line: &str) -> Option<Result<(Priority, &str), ParseErrorKind>> {
+    let rest = line.strip_prefix('<')?;
+    let invalid = || {
+        let token = line.split_whitespace().next().unwrap_or_default();
+        ParseErrorKind::InvalidPriority(token.to_string())
+    };
+    let Some(close) = rest.find('>').filter(|&close| close <= 3) else {
+        return Some(Err(invalid()));
+    };
+    let digits = &rest[..close];
+    let well_formed = !digits.is_empty()
+        && digits.bytes().all(|byte| byte.is_ascii_digit())
+        && (digits == "0" || !digits.starts_with('0'));
+    let priority = well_formed
+        .then(|| digits.parse::<u8>().ok())
+        .flatten()
+        .and_then(Priority::from_value);
+    Some(
+        priority
+            .map(|priority| (priority, &rest[close + 1..]))
+            .ok_or_else(invalid),
+    )
+}
+
+/// Facility codes from RFC 5424 section 6.2.1, named as in `syslog.h`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Facility {
+    Kern,
+    User,
+    Mail,
+    Daemon,
+    Auth,
+    Syslog,
+    Lpr,
+    News,
+    Uucp,
+    Cron,
+    Authpriv,
+    Ftp,
+    Ntp,
+    Security,
+    Console,
+    Clock,
+    Local0,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+}
+
+impl Facility {
+    pub const ALL: [Facility; 24] = [
+        Facility::Kern,
+        Facility::User,
+        Facility::Mail,
+        Facility::Daemon,
+        Facility::Auth,
+        Facility::Syslog,
+        Facility::Lpr,
+        Facility::News,
+        Facility::Uucp,
+        Facility::Cron,
+        Facility::Authpriv,
+        Facility::Ftp,
+        Facility::Ntp,
+        Facility::Security,
+        Facility::Console,
+        Facility::Clock,
+        Facility::Local0,
+        Facility::Local1,
+        Facility::Local2,
+        Facility::Local3,
+        Facility::Local4,
+        Facility::Local5,
+        Facility::Local6,
+        Facility::Local7,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Facility::Kern => "kern",
+            Facility::User => "user",
+            Facility::Mail => "mail",
+            Facility::Daemon => "daemon",
+            Facility::Auth => "auth",
+            Facility::Syslog => "syslog",
+            Facility::Lpr => "lpr",
+            Facility::News => "news",
+            Facility::Uucp => "uucp",
+            Facility::Cron => "cron",
+            Facility::Authpriv => "authpriv",
+            Facility::Ftp => "ftp",
+            Facility::Ntp => "ntp",
+            Facility::Security => "security",
+            Facility::Console => "console",
+            Facility::Clock => "clock",
+            Facility::Local0 => "local0",
+            Facility::Local1 => "local1",
+            Facility::Local2 => "local2",
+            Facility::Local3 => "local3",
+            Facility::Local4 => "local4",
+            Facility::Local5 => "local5",
+            Facility::Local6 => "local6",
+            Facility::Local7 => "local7",
+        }
+    }
+}
+
+impl fmt::Display for Facility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Serialize for Facility {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+/// Severity levels from RFC 5424 section 6.2.1, most severe first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Emerg,
+    Alert,
+    Crit,
+    Err,
+    Warning,
+    Notice,
+    Info,
+    Debug,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 8] = [
+        Severity::Emerg,
+        Severity::Alert,
+        Severity::Crit,
+        Severity::Err,
+        Severity::Warning,
+        Severity::Notice,
+        Severity::Info,
+        Severity::Debug,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Emerg => "emerg",
+            Severity::Alert => "alert",
+            Severity::Crit => "crit",
+            Severity::Err => "err",
+            Severity::Warning => "warning",
+            Severity::Notice => "notice",
+            Severity::Info => "info",
+            Severity::Debug => "debug",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Serialize for Severity {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_facility_and_severity() {
+        let priority = Priority::from_value(34).unwrap();
+        assert_eq!(priority.facility, Facility::Auth);
+        assert_eq!(priority.severity, Severity::Crit);
+        assert_eq!(priority.value(), 34);
+        assert_eq!(
+            Priority::from_value(191).unwrap().facility,
+            Facility::Local7
+        );
+        assert_eq!(Priority::from_value(192), None);
+    }
+
+    #[test]
+    fn strip_priority_prefix() {
+        let (priority, rest) = strip_priority("<165>1 2003-10-11T22:14:15Z")
+            .unwrap()
+            .unwrap();
+        assert_eq!(priority.value(), 165);
+        assert_eq!(rest, "1 2003-10-11T22:14:15Z");
+        assert_eq!(strip_priority("<0>x").unwrap().unwrap().1, "x");
+        assert_eq!(strip_priority("Jul  1 09:00:55"), None);
+        for bad in ["<>x", "<034>x", "<192>x", "<1234>x", "<3a>x", "<34"] {
+            assert!(
+                matches!(
+                    strip_priority(bad),
+                    Some(Err(ParseErrorKind::InvalidPriority(_)))
+                ),
+                "{}",
+                bad
+            );
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use std::borrow::Cow;
+
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+
+use crate::entry::{LogEntry, Month, TimeOfDay};
+use crate::error::{ParseError, ParseErrorKind};
+use crate::priority;
+
+/// Header fields of an RFC 5424 line that have no BSD counterpart.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Rfc5424Fields {
+    pub version: u8,
+    /// PROCID as written; `None` for the `-` nil value. [`LogEntry::pid`]
+    /// holds it as a number when it is one, 0 otherwise.
+    pub procid: Option<String>,
+    pub msgid: Option<String>,
+    pub structured_data: Vec<StructuredElement>,
+}
+
+/// One `[id name="value" ...]` element of STRUCTURED-DATA.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StructuredElement {
+    pub id: String,
+    /// Parameters in the order written, with `\"`, `\\` and `\]` unescaped.
+    #[serde(serialize_with = "serialize_params")]
+    pub params: Vec<(String, String)>,
+}
+
+fn serialize_params<S: Serializer>(
+    params: &[(String, String)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(params.len()))?;
+    for (name, value) in params {
+        map.serialize_entry(name, value)?;
+    }
+    map.end()
+}
+
+/// Parses one RFC 5424 line:
+///
+/// ```text
+/// <PRI>VERSION TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA [MSG]
+/// <165>1 2003-10-11T22:14:15.003Z mymachine evntslog - ID47 [exampleSDID@32473 iut="3"] An application event
+/// ```
+///
+/// The entry's month, day and time are those written in the timestamp, in its
+/// own offset; `timestamp` and `timestamp_utc` are filled in directly since
+/// the year and offset are part of the line. A nil (`-`) hostname or app-name
+/// is kept as `-`. Lines with a nil timestamp are rejected, as there is no
+/// time to place them at. The message keeps its whitespace, minus a leading
+/// UTF-8 byte order mark.
+pub fn parse_rfc5424(line: &str) -> Result<LogEntry<'_>, ParseError> {
+    let at = |rest: &str| line.len() - rest.len();
+    let (priority, rest) = match priority::strip_priority(line) {
+        Some(Ok(stripped)) => stripped,
+        Some(Err(kind)) => return Err(ParseError::new(kind, 0)),
+        None => {
+            let token = line.split_whitespace().next().unwrap_or_default();
+            return Err(ParseError::new(
+                ParseErrorKind::InvalidPriority(token.to_string()),
+                0,
+            ));
+        }
+    };
+
+    let offset = at(rest);
+    let (version_str, rest) = next_field(rest);
+    let version = parse_version(version_str).map_err(|kind| ParseError::new(kind, offset))?;
+
+    let offset = at(rest);
+    let (timestamp_str, rest) = next_field(rest);
+    let timestamp = DateTime::parse_from_rfc3339(timestamp_str).map_err(|_| {
+        ParseError::new(
+            ParseErrorKind::InvalidTimestamp(timestamp_str.to_string()),
+            offset,
+        )
+    })?;
+
+    let offset = at(rest);
+    let (hostname, rest) = next_field(rest);
+    if hostname.is_empty() {
+        return Err(ParseError::new(ParseErrorKind::MissingHostname, offset));
+    }
+    let offset = at(rest);
+    let (app_name, rest) = next_field(rest);
+    if app_name.is_empty() {
+        return Err(ParseError::new(ParseErrorKind::MissingProcess, offset));
+    }
+    let offset = at(rest);
+    let (procid, rest) = next_field(rest);
+    if procid.is_empty() {
+        return Err(ParseError::new(
+            ParseErrorKind::MissingPid(app_name.to_string()),
+            offset,
+        ));
+    }
+    let (msgid, rest) = next_field(rest);
+
+    let offset = at(rest);
+    let (structured_data, rest) =
+        parse_structured_data(rest).map_err(|kind| ParseError::new(kind, offset))?;
+    let message = match rest.strip_prefix(' ') {
+        Some(message) => message.strip_prefix('\u{feff}').unwrap_or(message),
+        None if rest.is_empty() => "",
+        None => {
+            return Err(ParseError::new(
+                ParseErrorKind::InvalidStructuredData(rest.to_string()),
+                at(rest),
+            ));
+        }
+    };
+
+    let local = timestamp.naive_local();
+    Ok(LogEntry {
+        month: Month::ALL[local.month0() as usize],
+        day: local.day() as u8,
+        time: TimeOfDay {
+            hour: local.hour() as u8,
+            minute: local.minute() as u8,
+            second: local.second() as u8,
+        },
+        hostname: Cow::Borrowed(hostname),
+        process: Cow::Borrowed(app_name),
+        pid: procid.parse().unwrap_or(0),
+        message: Cow::Borrowed(message),
+        timestamp: Some(local),
+        timestamp_utc: Some(timestamp.with_timezone(&Utc)),
+        priority: Some(priority),
+        rfc5424: Some(Rfc5424Fields {
+            version,
+            procid: nil_or(procid),
+            msgid: nil_or(msgid),
+            structured_data,
+        }),
+        unified: None,
+    })
+}
+
+/// Splits off a header field, which runs to the next single space.
+fn next_field(rest: &str) -> (&str, &str) {
+    rest.split_once(' ').unwrap_or((rest, ""))
+}
+
+fn nil_or(field: &str) -> Option<String> {
+    (field != "-").then(|| field.to_string())
+}
+
+fn parse_version(version_str: &str) -> Result<u8, ParseErrorKind> {
+    let invalid = || ParseErrorKind::InvalidVersion(version_str.to_string());
+    if version_str.starts_with('0') || !version_str.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    version_str.parse().map_err(|_| invalid())
+}
+
+/// Parses STRUCTURED-DATA at the start of `rest` (`-` or one or more
+/// `[...]` elements) and returns the elements and what follows them.
+fn parse_structured_data(rest: &str) -> Result<(Vec<StructuredElement>, &str), ParseErrorKind> {
+    if let Some(after) = rest.strip_prefix('-') {
+        return Ok((Vec::new(), after));
+    }
+    let invalid = || {
+        let token = rest.split_whitespace().next().unwrap_or_default();
+        ParseErrorKind::InvalidStructuredData(token.to_string())
+    };
+    let mut elements = Vec::new();
+    let mut remaining = rest;
+    while let Some(body) = remaining.strip_prefix('[') {
+        let id_end = body.find([' ', ']', '=', '"']).ok_or_else(invalid)?;
+        let id = &body[..id_end];
+        if id.is_empty() {
+            return Err(invalid());
+        }
+        let mut element = StructuredElement {
+            id: id.to_string(),
+            params: Vec::new(),
+        };
+        let mut cursor = &body[id_end..];
+        loop {
+            if let Some(after) = cursor.strip_prefix(']') {
+                remaining = after;
+                break;
+            }
+            let param = cursor.strip_prefix(' ').ok_or_else(invalid)?;
+            let (name, value_start) = param.split_once("=\"").ok_or_else(invalid)?;
+            if name.is_empty() || name.contains([' ', ']', '"']) {
+                return Err(invalid());
+            }
+            let (value, after) = parse_param_value(value_start).ok_or_else(invalid)?;
+            element.params.push((name.to_string(), value));
+            cursor = after;
+        }
+        elements.push(element);
+    }
+    if elements.is_empty() {
+        return Err(invalid());
+    }
+    Ok((elements, remaining))
+}
+
+/// Reads a PARAM-VALUE up to its closing unescaped `"`.
+fn parse_param_value(text: &str) -> Option<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = text.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &text[index + 1..])),
+            '\\' => match chars.next()? {
+                (_, escaped @ ('"' | '\\' | ']')) => value.push(escaped),
+                // Any other backslash is part of the value.
+                (_, other) => {
+                    value.push('\\');
+                    value.push(other);
+                }
+            },
+            _ => value.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::priority::{Facility, Severity};
+
+    #[test]
+    fn parse_rfc_examples() {
+        let entry = parse_rfc5424(
+            "<34>1 2003-10-11T22:14:15.003Z mymachine.example.com su - ID47 - \u{feff}'su root' failed for lonvick on /dev/pts/8",
+        )
+        .unwrap();
+        let priority = entry.priority.unwrap();
+        assert_eq!(priority.facility, Facility::Auth);
+        assert_eq!(priority.severity, Severity::Crit);
+        assert_eq!(entry.month, Month::Oct);
+        assert_eq!(entry.day, 11);
+        assert_eq!(entry.time.to_string(), "22:14:15");
+        assert_eq!(entry.hostname, "mymachine.example.com");
+        assert_eq!(entry.process, "su");
+        assert_eq!(entry.pid, 0);
+        assert_eq!(entry.message, "'su root' failed for lonvick on /dev/pts/8");
+        let fields = entry.rfc5424.unwrap();
+        assert_eq!(fields.version, 1);
+        assert_eq!(fields.procid, None);
+        assert_eq!(fields.msgid.as_deref(), Some("ID47"));
+        assert!(fields.structured_data.is_empty());
+
+        let entry = parse_rfc5424(
+            "<165>1 2003-08-24T05:14:15.000003-07:00 192.0.2.1 myproc 8710 - - %% It's time to make the do-nuts.",
+        )
+        .unwrap();
+        assert_eq!(entry.pid, 8710);
+        assert_eq!(entry.time.to_string(), "05:14:15");
+        assert_eq!(
+            entry.timestamp_utc.unwrap().to_rfc3339(),
+            "2003-08-24T12:14:15.000003+00:00"
+        );
+        assert_eq!(entry.message, "%% It's time to make the do-nuts.");
+    }
+
+    #[test]
+    fn parse_structured_data_elements() {
+        let entry = parse_rfc5424(
+            r#"<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [exampleSDID@32473 iut="3" eventSource="Application" eventID="1011"][examplePriority@32473 class="high" note="a \"quoted\" \] \x"]"#,
+        )
+        .unwrap();
+        assert_eq!(entry.message, "");
+        let elements = entry.rfc5424.unwrap().structured_data;
+        assert_eq!(elements.len(), 2);
+        assert_eq!(elements[0].id, "exampleSDID@32473");
+        assert_eq!(
+            elements[0].params[1],
+            ("eventSource".to_string(), "Application".to_string())
+        );
+        assert_eq!(elements[1].params[1].1, r#"a "quoted" ] \x"#);
+        assert_eq!(
+            serde_json::to_value(&elements[0]).unwrap(),
+            serde_json::json!({
+                "id": "exampleSDID@32473",
+                "params": { "iut": "3", "eventSource": "Application", "eventID": "1011" }
+            })
+        );
+    }
+
+    #[test]
+    fn message_keeps_its_whitespace() {
+        let entry =
+            parse_rfc5424("<14>1 2024-07-01T09:00:55Z host app 42 - -   indented  text").unwrap();
+        assert_eq!(entry.message, "  indented  text");
+        assert_eq!(entry.rfc5424.unwrap().procid.as_deref(), Some("42"));
+    }
+
+    #[test]
+    fn rejected_rfc5424_lines() {
+        let kind = |line: &str| parse_rfc5424(line).unwrap_err().kind.name();
+        assert_eq!(
+            kind("Jul  1 09:00:55 host kernel[0]: x"),
+            "invalid_priority"
+        );
+        assert_eq!(
+            kind("<14>0 2024-07-01T09:00:55Z h a - - -"),
+            "invalid_version"
+        );
+        assert_eq!(kind("<14>1 - h a - - -"), "invalid_timestamp");
+        assert_eq!(kind("<14>1 2024-07-01 h a - - -"), "invalid_timestamp");
+        assert_eq!(kind("<14>1 2024-07-01T09:00:55Z"), "missing_hostname");
+        assert_eq!(
+            kind("<14>1 2024-07-01T09:00:55Z h a - - [id"),
+            "invalid_structured_data"
+        );
+        assert_eq!(
+            kind("<14>1 2024-07-01T09:00:55Z h a - - [id x]"),
+            "invalid_structured_data"
+        );
+        assert_eq!(
+            kind("<14>1 2024-07-01T09:00:55Z h a - - msg"),
+            "invalid_structured_data"
+        );
+        let err = parse_rfc5424("<14>1 2024-13-01T09:00:55Z h a - - -").unwrap_err();
+        assert_eq!(err.offset, 6);
+    }
+}
@@ -0,0 +1,66 @@
+// Compiled into the tests of both the library and the binary, which each
+// use part of it.
+#![allow(dead_code)]
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// A file or directory under the system's temporary directory for a test,
+/// removed when dropped, so a failing assertion does not leave it behind.
+/// Its name includes the process ID, and `name` must be unique among the
+/// tests of a crate, which run in parallel.
+pub struct Scratch {
+    path: PathBuf,
+}
+
+impl Scratch {
+    /// A file named `name` holding `contents`.
+    pub fn file(name: &str, contents: impl AsRef<[u8]>) -> Self {
+        let scratch = Self::new(name);
+        fs::write(&scratch.path, contents).unwrap();
+        scratch
+    }
+
+    /// An empty directory named `name`.
+    pub fn dir(name: &str) -> Self {
+        let scratch = Self::new(name);
+        fs::create_dir_all(&scratch.path).unwrap();
+        scratch
+    }
+
+    fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("log_analyzer-{}-{}", std::process::id(), name));
+        let scratch = Scratch { path };
+        scratch.remove();
+        scratch
+    }
+
+    fn remove(&self) {
+        let _ = match fs::symlink_metadata(&self.path) {
+            Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(&self.path),
+            _ => fs::remove_file(&self.path),
+        };
+    }
+}
+
+impl Deref for Scratch {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for Scratch {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
@@ -0,0 +1,1112 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+use phf::{Set, phf_set};
+
+/// A built-in stopword list, chosen with `--stopword-preset`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Preset {
+    /// Common English words: articles, pronouns, auxiliaries and the like.
+    English,
+    /// Words that appear throughout macOS and syslog messages without saying
+    /// much: `kernel`, `pid`, `null`, Apple bundle IDs (`com.apple.*`), path
+    /// components such as `var` and `library`, units.
+    LogNoise,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 2] = [Preset::English, Preset::LogNoise];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::English => "english",
+            Preset::LogNoise => "log-noise",
+        }
+    }
+
+    /// The preset's words, already in the form the tokenizer produces.
+    pub fn words(self) -> &'static Set<&'static str> {
+        match self {
+            Preset::English => &ENGLISH,
+            Preset::LogNoise => &LOG_NOISE,
+        }
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Preset::ALL
+            .into_iter()
+            .find(|preset| preset.name() == name)
+            .ok_or_else(|| {
+                let names = Preset::ALL.map(Preset::name).join(", ");
+                format!("unknown stopword preset {name} (expected one of: {names})")
+            })
+    }
+}
+
+/// The words left out of `top_keywords`: any number of presets plus words
+/// of the user's own.
+///
+/// Words are compared the way [`crate::keywords::Tokenizer`] writes them by
+/// default, lowercased and without apostrophes, so `Isn't` in a stopword
+/// file also removes `isnt`. A word ending in `.*` stands for a dotted
+/// identifier and everything under it, so `com.apple.*` removes `com.apple`
+/// and `com.apple.xpc.launchd`, which the tokenizer keeps whole.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stopwords {
+    presets: Vec<Preset>,
+    words: HashSet<String>,
+    /// `.*` words without their `*`, such as `com.apple.`.
+    prefixes: Vec<String>,
+}
+
+impl Default for Stopwords {
+    /// The English preset.
+    fn default() -> Self {
+        Self::new().with_preset(Preset::English)
+    }
+}
+
+impl Stopwords {
+    /// No stopwords at all.
+    pub fn new() -> Self {
+        Self {
+            presets: Vec::new(),
+            words: HashSet::new(),
+            prefixes: Vec::new(),
+        }
+    }
+
+    pub fn with_preset(mut self, preset: Preset) -> Self {
+        if !self.presets.contains(&preset) {
+            self.presets.push(preset);
+            self.add_prefixes(preset.words().iter().copied());
+        }
+        self
+    }
+
+    pub fn with_words<S: AsRef<str>>(mut self, words: impl IntoIterator<Item = S>) -> Self {
+        let words = words
+            .into_iter()
+            .map(|word| normalize(word.as_ref()))
+            .collect::<Vec<_>>();
+        self.add_prefixes(words.iter().map(String::as_str));
+        self.words.extend(words);
+        self
+    }
+
+    fn add_prefixes<'w>(&mut self, words: impl Iterator<Item = &'w str>) {
+        for word in words {
+            let prefix = word
+                .strip_suffix('*')
+                .filter(|prefix| prefix.ends_with('.'));
+            if let Some(prefix) = prefix
+                && !self.prefixes.iter().any(|known| known == prefix)
+            {
+                self.prefixes.push(prefix.to_string());
+            }
+        }
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        let normalized = if word.is_ascii() {
+            !word.bytes().any(|b| b.is_ascii_uppercase() || b == b'\'')
+        } else {
+            !word.contains(|c: char| c.is_uppercase() || c == '\'')
+        };
+        if !normalized {
+            return self.contains_normalized(&normalize(word));
+        }
+        self.contains_normalized(word)
+    }
+
+    fn contains_normalized(&self, word: &str) -> bool {
+        self.presets
+            .iter()
+            .any(|preset| preset.words().contains(word))
+            || self.words.contains(word)
+            || (word.contains('.') && self.prefixes.iter().any(|prefix| under(word, prefix)))
+    }
+}
+
+/// Whether `word` is the identifier `prefix` names, such as `com.apple` for
+/// `com.apple.`, or one below it.
+fn under(word: &str, prefix: &str) -> bool {
+    word.starts_with(prefix) || prefix.strip_suffix('.') == Some(word)
+}
+
+fn normalize(word: &str) -> String {
+    word.to_lowercase().replace('\'', "")
+}
+
+/// Reads a stopword file: words separated by whitespace, usually one per
+/// line, with `#` starting a comment.
+pub fn parse_stopwords(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|line| line.split_once('#').map_or(line, |(words, _)| words))
+        .flat_map(str::split_whitespace)
+        .map(str::to_string)
+        .collect()
+}
+
+static ENGLISH: Set<&'static str> = phf_set! {
+    "able",
+    "about",
+    "above",
+    "abroad",
+    "according",
+    "accordingly",
+    "across",
+    "actually",
+    "adj",
+    "after",
+    "afterwards",
+    "again",
+    "against",
+    "ago",
+    "ahead",
+    "aint",
+    "all",
+    "allow",
+    "allows",
+    "almost",
+    "alone",
+    "along",
+    "alongside",
+    "already",
+    "also",
+    "although",
+    "always",
+    "am",
+    "amid",
+    "amidst",
+    "among",
+    "amongst",
+    "an",
+    "and",
+    "another",
+    "any",
+    "anybody",
+    "anyhow",
+    "anyone",
+    "anything",
+    "anyway",
+    "anyways",
+    "anywhere",
+    "apart",
+    "appear",
+    "appreciate",
+    "appropriate",
+    "are",
+    "arent",
+    "around",
+    "as",
+    "aside",
+    "ask",
+    "asking",
+    "associated",
+    "at",
+    "available",
+    "away",
+    "awfully",
+    "back",
+    "backward",
+    "backwards",
+    "be",
+    "became",
+    "because",
+    "become",
+    "becomes",
+    "becoming",
+    "been",
+    "before",
+    "beforehand",
+    "begin",
+    "behind",
+    "being",
+    "believe",
+    "below",
+    "beside",
+    "besides",
+    "best",
+    "better",
+    "between",
+    "beyond",
+    "both",
+    "brief",
+    "but",
+    "by",
+    "came",
+    "can",
+    "cannot",
+    "cant",
+    "caption",
+    "cause",
+    "causes",
+    "certain",
+    "certainly",
+    "changes",
+    "clearly",
+    "cmon",
+    "co",
+    "com",
+    "come",
+    "comes",
+    "concerning",
+    "consequently",
+    "consider",
+    "considering",
+    "contain",
+    "containing",
+    "contains",
+    "corresponding",
+    "could",
+    "couldnt",
+    "course",
+    "cs",
+    "currently",
+    "dare",
+    "darent",
+    "definitely",
+    "described",
+    "despite",
+    "did",
+    "didnt",
+    "different",
+    "directly",
+    "do",
+    "does",
+    "doesnt",
+    "doing",
+    "done",
+    "dont",
+    "down",
+    "downwards",
+    "during",
+    "each",
+    "edu",
+    "eg",
+    "eight",
+    "eighty",
+    "either",
+    "else",
+    "elsewhere",
+    "end",
+    "ending",
+    "enough",
+    "entirely",
+    "especially",
+    "et",
+    "etc",
+    "even",
+    "ever",
+    "evermore",
+    "every",
+    "everybody",
+    "everyone",
+    "everything",
+    "everywhere",
+    "ex",
+    "exactly",
+    "example",
+    "except",
+    "fairly",
+    "far",
+    "farther",
+    "few",
+    "fewer",
+    "fifth",
+    "first",
+    "five",
+    "followed",
+    "following",
+    "follows",
+    "for",
+    "forever",
+    "former",
+    "formerly",
+    "forth",
+    "forward",
+    "found",
+    "four",
+    "from",
+    "further",
+    "furthermore",
+    "get",
+    "gets",
+    "getting",
+    "given",
+    "gives",
+    "go",
+    "goes",
+    "going",
+    "gone",
+    "got",
+    "gotten",
+    "greetings",
+    "had",
+    "hadnt",
+    "half",
+    "happens",
+    "hardly",
+    "has",
+    "hasnt",
+    "have",
+    "havent",
+    "having",
+    "he",
+    "hed",
+    "hell",
+    "hello",
+    "help",
+    "hence",
+    "her",
+    "here",
+    "hereafter",
+    "hereby",
+    "herein",
+    "heres",
+    "hereupon",
+    "hers",
+    "herself",
+    "hes",
+    "hi",
+    "him",
+    "himself",
+    "his",
+    "hither",
+    "hopefully",
+    "how",
+    "howbeit",
+    "however",
+    "hundred",
+    "id",
+    "ie",
+    "if",
+    "ignored",
+    "ill",
+    "im",
+    "immediate",
+    "in",
+    "inasmuch",
+    "inc",
+    "indeed",
+    "indicate",
+    "indicated",
+    "indicates",
+    "inner",
+    "inside",
+    "insofar",
+    "instead",
+    "into",
+    "inward",
+    "is",
+    "isnt",
+    "it",
+    "itd",
+    "itll",
+    "its",
+    "itself",
+    "ive",
+    "just",
+    "k",
+    "keep",
+    "keeps",
+    "kept",
+    "know",
+    "known",
+    "knows",
+    "last",
+    "lately",
+    "later",
+    "latter",
+    "latterly",
+    "least",
+    "less",
+    "lest",
+    "let",
+    "lets",
+    "like",
+    "liked",
+    "likely",
+    "likewise",
+    "little",
+    "look",
+    "looking",
+    "looks",
+    "low",
+    "lower",
+    "ltd",
+    "made",
+    "mainly",
+    "make",
+    "makes",
+    "many",
+    "may",
+    "maybe",
+    "maynt",
+    "me",
+    "mean",
+    "meantime",
+    "meanwhile",
+    "merely",
+    "might",
+    "mightnt",
+    "mine",
+    "minus",
+    "miss",
+    "more",
+    "moreover",
+    "most",
+    "mostly",
+    "mr",
+    "mrs",
+    "much",
+    "must",
+    "mustnt",
+    "my",
+    "myself",
+    "name",
+    "namely",
+    "nd",
+    "near",
+    "nearly",
+    "necessary",
+    "need",
+    "neednt",
+    "needs",
+    "neither",
+    "never",
+    "neverf",
+    "neverless",
+    "nevertheless",
+    "new",
+    "next",
+    "nine",
+    "ninety",
+    "no",
+    "nobody",
+    "non",
+    "none",
+    "nonetheless",
+    "noone",
+    "no-one",
+    "nor",
+    "normally",
+    "not",
+    "nothing",
+    "notwithstanding",
+    "novel",
+    "now",
+    "nowhere",
+    "obviously",
+    "of",
+    "off",
+    "often",
+    "oh",
+    "ok",
+    "okay",
+    "old",
+    "on",
+    "once",
+    "one",
+    "ones",
+    "only",
+    "onto",
+    "opposite",
+    "or",
+    "other",
+    "others",
+    "otherwise",
+    "ought",
+    "oughtnt",
+    "our",
+    "ours",
+    "ourselves",
+    "out",
+    "outside",
+    "over",
+    "overall",
+    "own",
+    "particular",
+    "particularly",
+    "past",
+    "per",
+    "perhaps",
+    "placed",
+    "please",
+    "plus",
+    "possible",
+    "presumably",
+    "probably",
+    "provided",
+    "provides",
+    "que",
+    "quite",
+    "qv",
+    "rather",
+    "rd",
+    "re",
+    "really",
+    "reasonably",
+    "recent",
+    "recently",
+    "regarding",
+    "regardless",
+    "regards",
+    "relatively",
+    "respectively",
+    "right",
+    "round",
+    "said",
+    "same",
+    "saw",
+    "say",
+    "saying",
+    "says",
+    "second",
+    "secondly",
+    "see",
+    "seeing",
+    "seem",
+    "seemed",
+    "seeming",
+    "seems",
+    "seen",
+    "self",
+    "selves",
+    "sensible",
+    "sent",
+    "serious",
+    "seriously",
+    "seven",
+    "several",
+    "shall",
+    "shant",
+    "she",
+    "shed",
+    "shell",
+    "shes",
+    "should",
+    "shouldnt",
+    "since",
+    "six",
+    "so",
+    "some",
+    "somebody",
+    "someday",
+    "somehow",
+    "someone",
+    "something",
+    "sometime",
+    "sometimes",
+    "somewhat",
+    "somewhere",
+    "soon",
+    "sorry",
+    "specified",
+    "specify",
+    "specifying",
+    "still",
+    "sub",
+    "such",
+    "sup",
+    "sure",
+    "take",
+    "taken",
+    "taking",
+    "tell",
+    "tends",
+    "th",
+    "than",
+    "thank",
+    "thanks",
+    "thanx",
+    "that",
+    "thatll",
+    "thats",
+    "thatve",
+    "the",
+    "their",
+    "theirs",
+    "them",
+    "themselves",
+    "then",
+    "thence",
+    "there",
+    "thereafter",
+    "thereby",
+    "thered",
+    "therefore",
+    "therein",
+    "therell",
+    "therere",
+    "theres",
+    "thereupon",
+    "thereve",
+    "these",
+    "they",
+    "theyd",
+    "theyll",
+    "theyre",
+    "theyve",
+    "thing",
+    "things",
+    "think",
+    "third",
+    "thirty",
+    "this",
+    "thorough",
+    "thoroughly",
+    "those",
+    "though",
+    "three",
+    "through",
+    "throughout",
+    "thru",
+    "thus",
+    "till",
+    "to",
+    "together",
+    "too",
+    "took",
+    "toward",
+    "towards",
+    "tried",
+    "tries",
+    "truly",
+    "try",
+    "trying",
+    "ts",
+    "twice",
+    "two",
+    "un",
+    "under",
+    "underneath",
+    "undoing",
+    "unfortunately",
+    "unless",
+    "unlike",
+    "unlikely",
+    "until",
+    "unto",
+    "up",
+    "upon",
+    "upwards",
+    "us",
+    "use",
+    "used",
+    "useful",
+    "uses",
+    "using",
+    "usually",
+    "v",
+    "value",
+    "various",
+    "versus",
+    "very",
+    "via",
+    "viz",
+    "vs",
+    "want",
+    "wants",
+    "was",
+    "wasnt",
+    "way",
+    "we",
+    "wed",
+    "welcome",
+    "well",
+    "went",
+    "were",
+    "werent",
+    "weve",
+    "what",
+    "whatever",
+    "whatll",
+    "whats",
+    "whatve",
+    "when",
+    "whence",
+    "whenever",
+    "where",
+    "whereafter",
+    "whereas",
+    "whereby",
+    "wherein",
+    "wheres",
+    "whereupon",
+    "wherever",
+    "whether",
+    "which",
+    "whichever",
+    "while",
+    "whilst",
+    "whither",
+    "who",
+    "whod",
+    "whoever",
+    "whole",
+    "wholl",
+    "whom",
+    "whomever",
+    "whos",
+    "whose",
+    "why",
+    "will",
+    "willing",
+    "wish",
+    "with",
+    "within",
+    "without",
+    "wonder",
+    "wont",
+    "would",
+    "wouldnt",
+    "yes",
+    "yet",
+    "you",
+    "youd",
+    "youll",
+    "your",
+    "youre",
+    "yours",
+    "yourself",
+    "yourselves",
+    "youve",
+    "zero",
+    "a",
+    "hows",
+    "i",
+    "whens",
+    "whys",
+    "b",
+    "c",
+    "d",
+    "e",
+    "f",
+    "g",
+    "h",
+    "j",
+    "l",
+    "m",
+    "n",
+    "o",
+    "p",
+    "q",
+    "r",
+    "s",
+    "t",
+    "u",
+    "uucp",
+    "w",
+    "x",
+    "y",
+    "z",
+    "www",
+    "amount",
+    "bill",
+    "bottom",
+    "call",
+    "computer",
+    "con",
+    "cry",
+    "de",
+    "describe",
+    "detail",
+    "due",
+    "eleven",
+    "empty",
+    "fifteen",
+    "fifty",
+    "fill",
+    "find",
+    "fire",
+    "forty",
+    "front",
+    "full",
+    "give",
+    "herse",
+    "himse",
+    "interest",
+    "mill",
+    "move",
+    "part",
+    "put",
+    "show",
+    "side",
+    "sincere",
+    "sixty",
+    "system",
+    "ten",
+    "thick",
+    "thin",
+    "top",
+    "twelve",
+    "twenty",
+    "abst",
+    "accordance",
+    "act",
+    "added",
+    "adopted",
+    "affected",
+    "affecting",
+    "affects",
+    "ah",
+    "announce",
+    "anymore",
+    "apparently",
+    "approximately",
+    "aren",
+    "arise",
+    "auth",
+    "beginning",
+    "beginnings",
+    "begins",
+    "biol",
+    "briefly",
+    "ca",
+    "date",
+    "ed",
+    "effect",
+    "et-al",
+    "ff",
+    "fix",
+    "gave",
+    "giving",
+    "hid",
+    "home",
+    "immediately",
+    "importance",
+    "important",
+    "index",
+    "information",
+    "invention",
+    "keys",
+    "kg",
+    "km",
+    "largely",
+    "line",
+    "ll",
+    "means",
+    "mg",
+    "million",
+    "ml",
+    "mug",
+    "na",
+    "nay",
+    "necessarily",
+    "nos",
+    "noted",
+    "obtain",
+    "obtained",
+    "omitted",
+    "ord",
+    "owing",
+    "page",
+    "pages",
+    "poorly",
+    "possibly",
+    "potentially",
+    "pp",
+    "predominantly",
+    "present",
+    "previously",
+    "primarily",
+    "promptly",
+    "proud",
+    "quickly",
+    "ran",
+    "readily",
+    "ref",
+    "refs",
+    "related",
+    "research",
+    "resulted",
+    "resulting",
+    "results",
+    "run",
+    "sec",
+    "section",
+    "showed",
+    "shown",
+    "showns",
+    "shows",
+    "significant",
+    "significantly",
+    "similar",
+    "similarly",
+    "slightly",
+    "somethan",
+    "specifically",
+    "state",
+    "states",
+    "stop",
+    "strongly",
+    "substantially",
+    "successfully",
+    "sufficiently",
+    "suggest",
+    "thereof",
+    "thereto",
+    "thou",
+    "thoughh",
+    "thousand",
+    "throug",
+    "til",
+    "tip",
+    "ups",
+    "usefully",
+    "usefulness",
+    "ve",
+    "vol",
+    "vols",
+    "whim",
+    "widely",
+    "words",
+    "world",
+};
+
+static LOG_NOISE: Set<&'static str> = phf_set! {
+    "com.apple.*",
+    "0x0",
+    "kernel",
+    "com",
+    "apple",
+    "org",
+    "http",
+    "https",
+    "www",
+    "localhost",
+    "pid",
+    "uid",
+    "gid",
+    "tid",
+    "id",
+    "null",
+    "nil",
+    "none",
+    "true",
+    "false",
+    "unknown",
+    "default",
+    "var",
+    "usr",
+    "system",
+    "library",
+    "private",
+    "tmp",
+    "file",
+    "path",
+    "bundle",
+    "ms",
+    "sec",
+    "secs",
+    "seconds",
+    "usec",
+    "bytes",
+    "kb",
+    "mb",
+    "info",
+    "debug",
+    "notice",
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keywords::Tokenizer;
+
+    #[test]
+    fn presets_are_found_by_name() {
+        assert_eq!("log-noise".parse(), Ok(Preset::LogNoise));
+        assert_eq!(Preset::English.to_string(), "english");
+        assert!("french".parse::<Preset>().is_err());
+    }
+
+    #[test]
+    fn presets_remove_words_the_tokenizer_produces() {
+        let tokenizer = Tokenizer::new().keep_hex(true);
+        let message = "com.apple.xpc.launchd.domain.pid.WebContent.32623 isn't the kernel's \
+                       com.apple.Addres(31211) com.apple 0x0 com.applesauce";
+        let kept = |stopwords: Stopwords| {
+            tokenizer
+                .tokens(message)
+                .filter(|word| !stopwords.contains(word))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            kept(Stopwords::default()),
+            [
+                "com.apple.xpc.launchd.domain.pid.webcontent.32623",
+                "kernels",
+                "com.apple.addres",
+                "com.apple",
+                "0x0",
+                "com.applesauce"
+            ]
+        );
+        assert_eq!(
+            kept(Stopwords::new().with_preset(Preset::LogNoise)),
+            ["isnt", "the", "kernels", "com.applesauce"]
+        );
+        // The tokenizer above keeps hex, so this is the `--keep-hex` case.
+        assert!(
+            Stopwords::new()
+                .with_preset(Preset::LogNoise)
+                .contains("0x0")
+        );
+        assert_eq!(
+            kept(Stopwords::new().with_words(["com.apple.xpc.*", "0x0"])),
+            [
+                "isnt",
+                "the",
+                "kernels",
+                "com.apple.addres",
+                "com.apple",
+                "com.applesauce"
+            ]
+        );
+    }
+
+    #[test]
+    fn presets_hold_normalized_words() {
+        for preset in Preset::ALL {
+            for word in preset.words() {
+                assert_eq!(*word, normalize(word), "{preset} preset");
+            }
+        }
+    }
+
+    #[test]
+    fn stopwords_combine_presets_and_words() {
+        let stopwords = Stopwords::default();
+        assert!(stopwords.contains("the"));
+        assert!(stopwords.contains("The"));
+        assert!(!stopwords.contains("kernel"));
+
+        let stopwords = Stopwords::new()
+            .with_preset(Preset::LogNoise)
+            .with_words(["WWEN", "Isn't"]);
+        assert!(!stopwords.contains("the"));
+        assert!(stopwords.contains("kernel"));
+        assert!(stopwords.contains("wwen"));
+        assert!(stopwords.contains("isnt"));
+    }
+
+    #[test]
+    fn parse_a_stopword_file() {
+        let text = "# Wi-Fi chatter\narpt wl0\n\nairport_brcm43xx  # driver\n";
+        assert_eq!(parse_stopwords(text), ["arpt", "wl0", "airport_brcm43xx"]);
+    }
+}
@@ -0,0 +1,1082 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use chrono::{DateTime, Datelike, NaiveDateTime, Utc};
+use serde::Serialize;
+
+use crate::entry::LogEntry;
+use crate::error::ParseError;
+use crate::format::LogFormat;
+use crate::keywords::{Token, Tokenizer};
+use crate::stopwords::Stopwords;
+use crate::templates::{Template, TemplateMiner, template_ids};
+
+/// The document written to `summary.json`.
+///
+/// Field names and types are part of the output schema documented in the
+/// README; maps are ordered by key so the file is stable between runs.
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub total_entries: usize,
+    pub by_process: BTreeMap<String, usize>,
+    pub by_hostname: BTreeMap<String, usize>,
+    /// Entries per facility and severity, for lines with a `<PRI>`.
+    pub by_facility: BTreeMap<String, usize>,
+    pub by_severity: BTreeMap<String, usize>,
+    pub most_frequent_process: String,
+    pub most_frequent_hostname: String,
+    pub top_keywords: Vec<TermCount>,
+    /// Most common phrases of two or three adjacent words.
+    pub top_phrases: Vec<TermCount>,
+    /// The keywords most distinctive of each process in `by_process`, by
+    /// TF-IDF with each process's messages as one document. Empty unless
+    /// [`Analyzer::score_process_keywords`] is set.
+    pub process_keywords: BTreeMap<String, Vec<TermScore>>,
+    /// Event templates mined from messages, most common first. Empty unless
+    /// the analyzer has a [`TemplateMiner`].
+    pub templates: Vec<TemplateCount>,
+    /// Entries per template ID for each process in `by_process`.
+    pub process_templates: BTreeMap<String, BTreeMap<String, usize>>,
+    /// Lines that failed to parse, keyed by [`crate::ParseErrorKind::name`].
+    pub rejected_lines: BTreeMap<String, usize>,
+    /// Lines joined onto the entry before them rather than parsed on their own.
+    pub continuation_lines: usize,
+    /// Per-input totals, keyed by path as given or expanded on the command line.
+    pub by_file: BTreeMap<String, FileTotals>,
+    /// Earliest and latest local timestamps as written in the logs.
+    pub first_timestamp: Option<NaiveDateTime>,
+    pub last_timestamp: Option<NaiveDateTime>,
+    /// Earliest and latest entries in UTC, comparable across zones.
+    pub first_timestamp_utc: Option<DateTime<Utc>>,
+    pub last_timestamp_utc: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FileTotals {
+    pub total_entries: usize,
+    pub rejected_lines: BTreeMap<String, usize>,
+    pub continuation_lines: usize,
+    /// Year inferred for the file's first timestamped entry.
+    pub year: Option<i32>,
+    /// Name of the line format the file was read as.
+    pub format: Option<String>,
+}
+
+/// A keyword or phrase with the number of times it occurred.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TermCount {
+    pub term: String,
+    pub count: usize,
+    /// Fraction of all keyword occurrences (or, for phrases, all phrase
+    /// occurrences) that are this term, rounded to four places.
+    pub share: f64,
+}
+
+/// A keyword's TF-IDF score within one process.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TermScore {
+    pub term: String,
+    /// Occurrences in the process's messages.
+    pub count: usize,
+    /// The term's share of the process's keywords times the log of the
+    /// number of processes over the number that use it, rounded to four
+    /// places. Terms every process uses score 0 and are left out.
+    pub score: f64,
+}
+
+/// A message template with the number of entries it matched.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TemplateCount {
+    /// A hash of `template`, the same in every run; see
+    /// [`crate::templates::template_ids`].
+    pub id: String,
+    /// The message with its variable parts replaced by `<*>`.
+    pub template: String,
+    pub count: usize,
+}
+
+impl Summary {
+    pub fn write_json(&self, writer: impl Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(writer, self).map_err(io::Error::from)
+    }
+
+    pub fn write_text(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "total entries:          {}", self.total_entries)?;
+        writeln!(
+            writer,
+            "most frequent process:  {}",
+            self.most_frequent_process
+        )?;
+        writeln!(
+            writer,
+            "most frequent hostname: {}",
+            self.most_frequent_hostname
+        )?;
+        if let (Some(first), Some(last)) = (self.first_timestamp, self.last_timestamp) {
+            writeln!(writer, "time range (local):     {} to {}", first, last)?;
+        }
+        if let (Some(first), Some(last)) = (self.first_timestamp_utc, self.last_timestamp_utc) {
+            writeln!(writer, "time range (UTC):       {} to {}", first, last)?;
+        }
+        write_counts(&mut writer, "by process", by_count(&self.by_process))?;
+        write_counts(&mut writer, "by hostname", by_count(&self.by_hostname))?;
+        if !self.by_facility.is_empty() {
+            write_counts(&mut writer, "by facility", by_count(&self.by_facility))?;
+            write_counts(&mut writer, "by severity", by_count(&self.by_severity))?;
+        }
+        write_terms(&mut writer, "top keywords", &self.top_keywords)?;
+        write_terms(&mut writer, "top phrases", &self.top_phrases)?;
+        writeln!(writer, "process keywords:")?;
+        for (process, terms) in by_process_count(&self.process_keywords, &self.by_process) {
+            let terms = terms
+                .iter()
+                .map(|t| format!("{} ({:.3})", t.term, t.score))
+                .collect::<Vec<_>>();
+            writeln!(writer, "  {}: {}", process, terms.join(", "))?;
+        }
+        writeln!(writer, "templates:")?;
+        for template in &self.templates {
+            writeln!(
+                writer,
+                "  {:>7}  {}  {}",
+                template.count, template.id, template.template
+            )?;
+        }
+        write_counts(
+            &mut writer,
+            "rejected lines",
+            by_count(&self.rejected_lines),
+        )?;
+        writeln!(
+            writer,
+            "continuation lines:     {}",
+            self.continuation_lines
+        )?;
+        writeln!(writer, "by file:")?;
+        for (path, totals) in &self.by_file {
+            let rejected: usize = totals.rejected_lines.values().sum();
+            write!(
+                writer,
+                "  {:>7}  {} ({} rejected",
+                totals.total_entries, path, rejected
+            )?;
+            if totals.continuation_lines > 0 {
+                write!(writer, ", {} continuation", totals.continuation_lines)?;
+            }
+            if let Some(year) = totals.year {
+                write!(writer, ", year {}", year)?;
+            }
+            if let Some(format) = &totals.format {
+                write!(writer, ", {}", format)?;
+            }
+            writeln!(writer, ")")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct NameCount {
+    pub name: String,
+    pub count: usize,
+}
+
+/// Top-N view of an [`Analyzer`], used by the `stats` command.
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub total_entries: usize,
+    pub top_processes: Vec<NameCount>,
+    pub top_hostnames: Vec<NameCount>,
+    pub top_keywords: Vec<TermCount>,
+    pub top_phrases: Vec<TermCount>,
+    pub rejected_lines: usize,
+    pub continuation_lines: usize,
+}
+
+impl Stats {
+    pub fn write_json(&self, writer: impl Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(writer, self).map_err(io::Error::from)
+    }
+
+    pub fn write_text(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "total entries:  {}", self.total_entries)?;
+        writeln!(writer, "rejected lines: {}", self.rejected_lines)?;
+        writeln!(writer, "continuations:  {}", self.continuation_lines)?;
+        write_counts(
+            &mut writer,
+            "top processes",
+            name_counts(&self.top_processes),
+        )?;
+        write_counts(
+            &mut writer,
+            "top hostnames",
+            name_counts(&self.top_hostnames),
+        )?;
+        write_terms(&mut writer, "top keywords", &self.top_keywords)?;
+        write_terms(&mut writer, "top phrases", &self.top_phrases)
+    }
+}
+
+fn write_counts(
+    writer: &mut impl Write,
+    title: &str,
+    counts: Vec<(&str, usize)>,
+) -> io::Result<()> {
+    writeln!(writer, "{}:", title)?;
+    for (name, count) in counts {
+        writeln!(writer, "  {:>7}  {}", count, name)?;
+    }
+    Ok(())
+}
+
+fn write_terms(writer: &mut impl Write, title: &str, terms: &[TermCount]) -> io::Result<()> {
+    writeln!(writer, "{}:", title)?;
+    for term in terms {
+        writeln!(
+            writer,
+            "  {:>7}  {:>5.1}%  {}",
+            term.count,
+            term.share * 100.0,
+            term.term
+        )?;
+    }
+    Ok(())
+}
+
+/// The entries of `terms` with any, busiest process first.
+fn by_process_count<'a>(
+    terms: &'a BTreeMap<String, Vec<TermScore>>,
+    by_process: &BTreeMap<String, usize>,
+) -> Vec<(&'a str, &'a [TermScore])> {
+    let mut sorted = terms
+        .iter()
+        .filter(|(_, terms)| !terms.is_empty())
+        .map(|(process, terms)| (process.as_str(), terms.as_slice()))
+        .collect::<Vec<_>>();
+    sorted.sort_by_key(|&(process, _)| {
+        std::cmp::Reverse(by_process.get(process).copied().unwrap_or(0))
+    });
+    sorted
+}
+
+fn name_counts(list: &[NameCount]) -> Vec<(&str, usize)> {
+    list.iter().map(|n| (n.name.as_str(), n.count)).collect()
+}
+
+fn by_count(map: &BTreeMap<String, usize>) -> Vec<(&str, usize)> {
+    let mut sorted = map
+        .iter()
+        .map(|(name, &count)| (name.as_str(), count))
+        .collect::<Vec<_>>();
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    sorted
+}
+
+/// Numbers of adjacent words counted as phrases: bigrams and trigrams.
+const PHRASE_LENGTHS: RangeInclusive<usize> = 2..=3;
+
+/// Accumulates per-process, per-host and keyword counts one line at a time.
+pub struct Analyzer {
+    tokenizer: Tokenizer,
+    stopwords: Stopwords,
+    total_entries: usize,
+    process_freq: HashMap<String, usize>,
+    host_name_freq: HashMap<String, usize>,
+    by_facility: BTreeMap<String, usize>,
+    by_severity: BTreeMap<String, usize>,
+    keywords: TermFreq,
+    phrases: TermFreq,
+    /// Keywords per process, the documents for TF-IDF, if they are scored.
+    process_keywords: Option<HashMap<String, TermFreq>>,
+    /// Mines `templates` only when one is given, as only the summary reports
+    /// them.
+    templates: Option<TemplateMiner>,
+    /// Entries per template, by index in the miner, for each process.
+    process_templates: HashMap<String, HashMap<usize, usize>>,
+    /// Scratch space for building phrases, kept to avoid an allocation each.
+    phrase: String,
+    top_terms: Option<usize>,
+    rejected_lines: BTreeMap<String, usize>,
+    continuation_lines: usize,
+    by_file: BTreeMap<String, FileTotals>,
+    current_file: Option<String>,
+    first_timestamp: Option<NaiveDateTime>,
+    last_timestamp: Option<NaiveDateTime>,
+    first_timestamp_utc: Option<DateTime<Utc>>,
+    last_timestamp_utc: Option<DateTime<Utc>>,
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Analyzer {
+    pub fn new() -> Self {
+        Self {
+            tokenizer: Tokenizer::new(),
+            stopwords: Stopwords::default(),
+            total_entries: 0,
+            process_freq: HashMap::new(),
+            host_name_freq: HashMap::new(),
+            by_facility: BTreeMap::new(),
+            by_severity: BTreeMap::new(),
+            keywords: TermFreq::default(),
+            phrases: TermFreq::default(),
+            process_keywords: None,
+            templates: None,
+            process_templates: HashMap::new(),
+            phrase: String::new(),
+            top_terms: None,
+            rejected_lines: BTreeMap::new(),
+            continuation_lines: 0,
+            by_file: BTreeMap::new(),
+            current_file: None,
+            first_timestamp: None,
+            last_timestamp: None,
+            first_timestamp_utc: None,
+            last_timestamp_utc: None,
+        }
+    }
+
+    /// Replaces the default [`Tokenizer`] that splits messages into keywords.
+    pub fn with_tokenizer(mut self, tokenizer: Tokenizer) -> Self {
+        self.tokenizer = tokenizer;
+        self
+    }
+
+    /// Replaces the default English [`Stopwords`].
+    pub fn with_stopwords(mut self, stopwords: Stopwords) -> Self {
+        self.stopwords = stopwords;
+        self
+    }
+
+    /// Groups messages into [`Summary::templates`] with `templates`. Without
+    /// a miner no templates are reported.
+    pub fn with_template_miner(mut self, templates: TemplateMiner) -> Self {
+        self.templates = Some(templates);
+        self
+    }
+
+    /// Keeps each process's keywords to report [`Summary::process_keywords`].
+    /// Off by default, as the per-process documents cost time and memory.
+    pub fn score_process_keywords(mut self, score: bool) -> Self {
+        self.process_keywords = score.then(HashMap::new);
+        self
+    }
+
+    /// Reports this many keywords and phrases instead of the top-N given to
+    /// [`Analyzer::summary`] or [`Analyzer::stats`].
+    pub fn with_top_terms(mut self, top_terms: usize) -> Self {
+        self.top_terms = Some(top_terms);
+        self
+    }
+
+    /// Attributes the lines recorded from now on to `path` in
+    /// [`Summary::by_file`]. Lines recorded before the first call are only
+    /// counted in the global totals.
+    pub fn start_file(&mut self, path: &Path) {
+        let name = path.display().to_string();
+        self.by_file.entry(name.clone()).or_default();
+        self.current_file = Some(name);
+    }
+
+    /// Records the line format of the current file.
+    pub fn set_format(&mut self, format: &LogFormat) {
+        if let Some(totals) = self.current_file_totals() {
+            totals.format = Some(format.name().to_string());
+        }
+    }
+
+    fn current_file_totals(&mut self) -> Option<&mut FileTotals> {
+        let name = self.current_file.as_ref()?;
+        self.by_file.get_mut(name)
+    }
+
+    pub fn record(&mut self, entry: &LogEntry<'_>) {
+        self.total_entries += 1;
+        if let Some(timestamp) = entry.timestamp {
+            self.first_timestamp =
+                Some(self.first_timestamp.map_or(timestamp, |t| t.min(timestamp)));
+            self.last_timestamp = Some(self.last_timestamp.map_or(timestamp, |t| t.max(timestamp)));
+        }
+        if let Some(utc) = entry.timestamp_utc {
+            self.first_timestamp_utc = Some(self.first_timestamp_utc.map_or(utc, |t| t.min(utc)));
+            self.last_timestamp_utc = Some(self.last_timestamp_utc.map_or(utc, |t| t.max(utc)));
+        }
+        if let Some(totals) = self.current_file_totals() {
+            totals.total_entries += 1;
+            if totals.year.is_none() {
+                totals.year = entry.timestamp.map(|timestamp| timestamp.year());
+            }
+        }
+        increment(&mut self.process_freq, &entry.process);
+        increment(&mut self.host_name_freq, &entry.hostname);
+        if let Some(priority) = entry.priority {
+            *self
+                .by_facility
+                .entry(priority.facility.name().to_string())
+                .or_insert(0) += 1;
+            *self
+                .by_severity
+                .entry(priority.severity.name().to_string())
+                .or_insert(0) += 1;
+        }
+        if let Some(templates) = &mut self.templates {
+            let template = templates.add(&entry.message);
+            *value_for(&mut self.process_templates, &entry.process)
+                .entry(template)
+                .or_insert(0) += 1;
+        }
+        let mut process_keywords = self
+            .process_keywords
+            .as_mut()
+            .map(|documents| value_for(documents, &entry.process));
+        // Each keyword of the current run, and whether it is a stopword.
+        let mut run = Vec::new();
+        for token in self.tokenizer.scan(&entry.message) {
+            match token {
+                Token::Word(word) => {
+                    let stopword = self.stopwords.contains(&word);
+                    if !stopword {
+                        self.keywords.add(&word);
+                        if let Some(process_keywords) = &mut process_keywords {
+                            process_keywords.add(&word);
+                        }
+                    }
+                    run.push((word, stopword));
+                }
+                Token::Break => {
+                    count_phrases(&mut self.phrases, &mut self.phrase, &run);
+                    run.clear();
+                }
+            }
+        }
+        count_phrases(&mut self.phrases, &mut self.phrase, &run);
+    }
+
+    pub fn reject(&mut self, err: &ParseError) {
+        self.total_entries += 1;
+        if let Some(totals) = self.current_file_totals() {
+            totals.total_entries += 1;
+            *totals
+                .rejected_lines
+                .entry(err.kind.name().to_string())
+                .or_insert(0) += 1;
+        }
+        *self
+            .rejected_lines
+            .entry(err.kind.name().to_string())
+            .or_insert(0) += 1;
+    }
+
+    /// Counts `count` lines that were joined onto an entry as continuations.
+    /// They are not entries of their own, so `total_entries` is unchanged.
+    pub fn add_continuation_lines(&mut self, count: usize) {
+        self.continuation_lines += count;
+        if let Some(totals) = self.current_file_totals() {
+            totals.continuation_lines += count;
+        }
+    }
+
+    pub fn summary(&self, top_n: usize) -> Summary {
+        let (templates, process_templates) = self.templates();
+        Summary {
+            total_entries: self.total_entries,
+            by_process: to_count_map(&self.process_freq),
+            by_hostname: to_count_map(&self.host_name_freq),
+            by_facility: self.by_facility.clone(),
+            by_severity: self.by_severity.clone(),
+            most_frequent_process: most_frequent(&self.process_freq),
+            most_frequent_hostname: most_frequent(&self.host_name_freq),
+            top_keywords: self.top_keywords(top_n),
+            top_phrases: self.top_phrases(top_n),
+            process_keywords: self.process_keywords(top_n),
+            templates,
+            process_templates,
+            rejected_lines: self.rejected_lines.clone(),
+            continuation_lines: self.continuation_lines,
+            by_file: self.by_file.clone(),
+            first_timestamp: self.first_timestamp,
+            last_timestamp: self.last_timestamp,
+            first_timestamp_utc: self.first_timestamp_utc,
+            last_timestamp_utc: self.last_timestamp_utc,
+        }
+    }
+
+    pub fn stats(&self, top_n: usize) -> Stats {
+        let top = |freq| {
+            sorted_by_count(freq)
+                .into_iter()
+                .take(top_n)
+                .map(|(name, count)| NameCount {
+                    name: name.to_string(),
+                    count,
+                })
+                .collect()
+        };
+        Stats {
+            total_entries: self.total_entries,
+            top_processes: top(&self.process_freq),
+            top_hostnames: top(&self.host_name_freq),
+            top_keywords: self.top_keywords(top_n),
+            top_phrases: self.top_phrases(top_n),
+            rejected_lines: self.rejected_lines.values().sum(),
+            continuation_lines: self.continuation_lines,
+        }
+    }
+
+    fn top_keywords(&self, top_n: usize) -> Vec<TermCount> {
+        self.keywords.top(self.top_terms.unwrap_or(top_n))
+    }
+
+    fn top_phrases(&self, top_n: usize) -> Vec<TermCount> {
+        self.phrases.top(self.top_terms.unwrap_or(top_n))
+    }
+
+    /// Template counts, and their counts per process, keyed by template ID.
+    /// Templates the miner kept apart but that generalized to the same text
+    /// are one event, and are counted together.
+    fn templates(
+        &self,
+    ) -> (
+        Vec<TemplateCount>,
+        BTreeMap<String, BTreeMap<String, usize>>,
+    ) {
+        let Some(miner) = &self.templates else {
+            return (Vec::new(), BTreeMap::new());
+        };
+        let texts = miner
+            .templates()
+            .iter()
+            .map(Template::text)
+            .collect::<Vec<_>>();
+        // Templates that ended up with the same text are the same event.
+        let mut counts = BTreeMap::<&str, usize>::new();
+        for (template, text) in miner.templates().iter().zip(&texts) {
+            *counts.entry(text).or_insert(0) += template.count();
+        }
+        let ids = template_ids(counts.keys().copied());
+        let mut templates = counts
+            .into_iter()
+            .map(|(text, count)| TemplateCount {
+                id: ids[text].clone(),
+                template: text.to_string(),
+                count,
+            })
+            .collect::<Vec<_>>();
+        templates.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.id.cmp(&b.id)));
+        let process_templates = self
+            .process_templates
+            .iter()
+            .map(|(process, counts)| {
+                let mut by_id = BTreeMap::new();
+                for (&index, &count) in counts {
+                    *by_id.entry(ids[texts[index].as_str()].clone()).or_insert(0) += count;
+                }
+                (process.clone(), by_id)
+            })
+            .collect();
+        (templates, process_templates)
+    }
+
+    fn process_keywords(&self, top_n: usize) -> BTreeMap<String, Vec<TermScore>> {
+        let Some(documents) = &self.process_keywords else {
+            return BTreeMap::new();
+        };
+        let top_n = self.top_terms.unwrap_or(top_n);
+        let mut processes_using = HashMap::<&str, usize>::new();
+        for keywords in documents.values() {
+            for term in keywords.counts.keys() {
+                *processes_using.entry(term).or_insert(0) += 1;
+            }
+        }
+        let processes = documents.len() as f64;
+        documents
+            .iter()
+            .map(|(process, keywords)| {
+                let mut scores = keywords
+                    .counts
+                    .iter()
+                    .map(|(term, &count)| {
+                        let tf = count as f64 / keywords.total as f64;
+                        let idf = (processes / processes_using[term.as_str()] as f64).ln();
+                        (term.as_str(), count, tf * idf)
+                    })
+                    .filter(|&(_, _, score)| score > 0.0)
+                    .collect::<Vec<_>>();
+                scores.sort_by(|a, b| b.2.total_cmp(&a.2).then_with(|| a.0.cmp(b.0)));
+                let terms = scores
+                    .into_iter()
+                    .take(top_n)
+                    .map(|(term, count, score)| TermScore {
+                        term: term.to_string(),
+                        count,
+                        score: round4(score),
+                    })
+                    .collect();
+                (process.clone(), terms)
+            })
+            .collect()
+    }
+}
+
+/// Occurrences of keywords or phrases, with their total for shares.
+#[derive(Default)]
+struct TermFreq {
+    counts: HashMap<String, usize>,
+    total: usize,
+}
+
+impl TermFreq {
+    fn add(&mut self, term: &str) {
+        increment(&mut self.counts, term);
+        self.total += 1;
+    }
+
+    fn top(&self, top_n: usize) -> Vec<TermCount> {
+        sorted_by_count(&self.counts)
+            .into_iter()
+            .take(top_n)
+            .map(|(term, count)| TermCount {
+                term: term.to_string(),
+                count,
+                share: round4(count as f64 / self.total as f64),
+            })
+            .collect()
+    }
+}
+
+fn round4(value: f64) -> f64 {
+    (value * 10_000.0).round() / 10_000.0
+}
+
+/// Counts the phrases of one run of adjacent keywords. Phrases neither
+/// start nor end with a stopword but may have one inside, as in `time of
+/// day`.
+fn count_phrases(phrases: &mut TermFreq, phrase: &mut String, run: &[(Cow<'_, str>, bool)]) {
+    for length in PHRASE_LENGTHS {
+        for words in run.windows(length) {
+            if words[0].1 || words[length - 1].1 {
+                continue;
+            }
+            phrase.clear();
+            for (i, (word, _)) in words.iter().enumerate() {
+                if i > 0 {
+                    phrase.push(' ');
+                }
+                phrase.push_str(word);
+            }
+            phrases.add(phrase);
+        }
+    }
+}
+
+/// Counts one more `key`, allocating only the first time it is seen.
+fn increment(freq: &mut HashMap<String, usize>, key: &str) {
+    match freq.get_mut(key) {
+        Some(count) => *count += 1,
+        None => {
+            freq.insert(key.to_string(), 1);
+        }
+    }
+}
+
+/// The value for `key`, inserting a default the first time it is seen so
+/// later calls do not allocate the key.
+fn value_for<'m, V: Default>(map: &'m mut HashMap<String, V>, key: &str) -> &'m mut V {
+    if !map.contains_key(key) {
+        map.insert(key.to_string(), V::default());
+    }
+    map.get_mut(key).expect("inserted above")
+}
+
+/// Highest count first; ties are broken by name so the output is deterministic.
+fn sorted_by_count(freq: &HashMap<String, usize>) -> Vec<(&str, usize)> {
+    let mut sorted = freq
+        .iter()
+        .map(|(name, &count)| (name.as_str(), count))
+        .collect::<Vec<_>>();
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    sorted
+}
+
+fn most_frequent(freq: &HashMap<String, usize>) -> String {
+    sorted_by_count(freq)
+        .first()
+        .map(|&(name, _)| name.to_string())
+        .unwrap_or_default()
+}
+
+fn to_count_map(freq: &HashMap<String, usize>) -> BTreeMap<String, usize> {
+    freq.iter()
+        .map(|(name, &count)| (name.clone(), count))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::templates::template_id;
+
+    #[test]
+    fn most_frequent_breaks_ties_by_name() {
+        let freq = HashMap::from([
+            ("kernel".to_string(), 5),
+            ("configd".to_string(), 5),
+            ("sandboxd".to_string(), 2),
+        ]);
+        assert_eq!(most_frequent(&freq), "configd");
+    }
+
+    #[test]
+    fn analyzer_counts_entries_and_rejections() {
+        let mut analyzer = Analyzer::new();
+        analyzer.start_file(Path::new("system.log"));
+        for line in [
+            "Jul  1 09:00:55 calvisitor-10-105-160-95 kernel[0]: AppleThunderboltNHIType2::prePCIWake - power up complete",
+            "Jul  1 09:01:05 calvisitor-10-105-160-95 com.apple.CDScheduler[43]: Thermal pressure state: 1",
+            "Jul  1 09:01:06 authorMacBook-Pro kernel[0]: ARPT: 620651.021206: wl0: wl_update_tcpkeep_seq",
+            "not a log line",
+        ] {
+            match line.parse::<LogEntry>() {
+                Ok(entry) => analyzer.record(&entry),
+                Err(err) => analyzer.reject(&err),
+            }
+        }
+        let summary = analyzer.summary(3);
+        assert_eq!(summary.total_entries, 4);
+        assert_eq!(summary.by_process["kernel"], 2);
+        assert_eq!(summary.by_hostname["calvisitor-10-105-160-95"], 2);
+        assert_eq!(summary.most_frequent_process, "kernel");
+        assert_eq!(summary.rejected_lines["invalid_month"], 1);
+        assert_eq!(summary.by_file["system.log"].total_entries, 4);
+
+        let stats = analyzer.stats(1);
+        assert_eq!(
+            stats.top_processes,
+            vec![NameCount {
+                name: "kernel".to_string(),
+                count: 2
+            }]
+        );
+        assert_eq!(stats.rejected_lines, 1);
+    }
+
+    #[test]
+    fn analyzer_keeps_per_file_totals() {
+        let mut analyzer = Analyzer::new();
+        analyzer.start_file(Path::new("system.log.1"));
+        analyzer.record(&"Jul  1 09:00:55 host kernel[0]: one".parse().unwrap());
+        analyzer.start_file(Path::new("system.log.0"));
+        analyzer.record(&"Jul  2 09:00:55 host kernel[0]: two".parse().unwrap());
+        analyzer.reject(&"xxx".parse::<LogEntry>().unwrap_err());
+        analyzer.start_file(Path::new("empty.log"));
+
+        let summary = analyzer.summary(3);
+        assert_eq!(summary.by_process["kernel"], 2);
+        assert_eq!(summary.by_file["system.log.1"].total_entries, 1);
+        assert_eq!(summary.by_file["system.log.0"].total_entries, 2);
+        assert_eq!(
+            summary.by_file["system.log.0"].rejected_lines["invalid_month"],
+            1
+        );
+        assert_eq!(summary.by_file["empty.log"], FileTotals::default());
+
+        analyzer.start_file(Path::new("system.log.0"));
+        analyzer.add_continuation_lines(2);
+        let summary = analyzer.summary(3);
+        assert_eq!(summary.continuation_lines, 2);
+        assert_eq!(summary.by_file["system.log.0"].continuation_lines, 2);
+        assert_eq!(summary.by_file["system.log.0"].total_entries, 2);
+        assert_eq!(summary.first_timestamp, None);
+    }
+
+    #[test]
+    fn keywords_are_tokenized_before_stopwords() {
+        let mut analyzer = Analyzer::new();
+        for line in [
+            "Jul  1 09:02:26 host kernel[0]: ARPT: 620701.011328: AirPort_Brcm43xx::syncPowerState: WWEN[enabled]",
+            "Jul  1 09:02:27 host kernel[0]: The wwen: 0x0",
+            "Jul  1 09:02:28 host kernel[0]: The WWEN",
+        ] {
+            analyzer.record(&line.parse().unwrap());
+        }
+        let keywords = analyzer
+            .summary(10)
+            .top_keywords
+            .into_iter()
+            .map(|k| k.term)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keywords,
+            [
+                "wwen",
+                "airport_brcm43xx",
+                "arpt",
+                "enabled",
+                "syncpowerstate"
+            ]
+        );
+    }
+
+    #[test]
+    fn keywords_and_phrases_are_counted_exactly() {
+        let mut analyzer = Analyzer::new().with_stopwords(Stopwords::new());
+        for line in [
+            "Jul  1 09:01:05 host com.apple.CDScheduler[43]: Thermal pressure state: 1 Memory pressure state: 0",
+            "Jul  1 09:28:50 host com.apple.CDScheduler[258]: Thermal pressure state: 1 Memory pressure state: 0",
+        ] {
+            analyzer.record(&line.parse().unwrap());
+        }
+        let summary = analyzer.summary(2);
+        assert_eq!(
+            summary.top_keywords,
+            vec![
+                TermCount {
+                    term: "pressure".to_string(),
+                    count: 4,
+                    share: 0.3333,
+                },
+                TermCount {
+                    term: "state".to_string(),
+                    count: 4,
+                    share: 0.3333,
+                },
+            ]
+        );
+        let phrases = analyzer
+            .with_top_terms(4)
+            .summary(2)
+            .top_phrases
+            .into_iter()
+            .map(|p| (p.term, p.count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            phrases,
+            [
+                ("pressure state".to_string(), 4),
+                ("memory pressure".to_string(), 2),
+                ("memory pressure state".to_string(), 2),
+                ("thermal pressure".to_string(), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn process_keywords_are_scored_by_tf_idf() {
+        let mut analyzer = Analyzer::new()
+            .with_stopwords(Stopwords::new())
+            .score_process_keywords(true);
+        for line in [
+            "Jul  1 09:00:55 host kernel[0]: alpha shared",
+            "Jul  1 09:00:56 host sandboxd[129]: beta shared",
+            "Jul  1 09:00:57 host symptomsd[215]: gamma gamma shared",
+        ] {
+            analyzer.record(&line.parse().unwrap());
+        }
+        let summary = analyzer.summary(5);
+        assert_eq!(
+            summary.process_keywords.keys().collect::<Vec<_>>(),
+            summary.by_process.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            summary.process_keywords["kernel"],
+            vec![TermScore {
+                term: "alpha".to_string(),
+                count: 1,
+                score: 0.5493,
+            }]
+        );
+        assert_eq!(
+            summary.process_keywords["symptomsd"],
+            vec![TermScore {
+                term: "gamma".to_string(),
+                count: 2,
+                score: 0.7324,
+            }]
+        );
+    }
+
+    #[test]
+    fn templates_are_counted_per_process() {
+        let mut analyzer = Analyzer::new().with_template_miner(TemplateMiner::new());
+        for line in [
+            "Jul  1 09:01:05 host com.apple.CDScheduler[43]: Thermal pressure state: 1 Memory pressure state: 0",
+            "Jul  1 09:04:00 host kernel[0]: en0: channel changed to 1",
+            "Jul  1 09:28:50 host com.apple.CDScheduler[258]: Thermal pressure state: 0 Memory pressure state: 0",
+            "Jul  1 09:30:00 host kernel[0]: en0: channel changed to 6",
+            "Jul  1 09:31:00 host airportd[86]: en0: channel changed to 11",
+        ] {
+            analyzer.record(&line.parse().unwrap());
+        }
+        let summary = analyzer.summary(3);
+        let channel = template_id("en0: channel changed to <*>");
+        let pressure = template_id("Thermal pressure state: <*> Memory pressure state: <*>");
+        assert_eq!(
+            summary.templates,
+            vec![
+                TemplateCount {
+                    id: channel.clone(),
+                    template: "en0: channel changed to <*>".to_string(),
+                    count: 3,
+                },
+                TemplateCount {
+                    id: pressure.clone(),
+                    template: "Thermal pressure state: <*> Memory pressure state: <*>".to_string(),
+                    count: 2,
+                },
+            ]
+        );
+        assert_eq!(
+            summary.process_templates,
+            BTreeMap::from([
+                (
+                    "airportd".to_string(),
+                    BTreeMap::from([(channel.clone(), 1)])
+                ),
+                (
+                    "com.apple.CDScheduler".to_string(),
+                    BTreeMap::from([(pressure, 2)])
+                ),
+                ("kernel".to_string(), BTreeMap::from([(channel, 2)])),
+            ])
+        );
+    }
+
+    #[test]
+    fn phrases_do_not_start_or_end_with_stopwords() {
+        let mut analyzer = Analyzer::new();
+        analyzer.record(
+            &"Jul  1 22:03:31 host com.apple.cts[258]: told me to run this job; however, but the start time isn't for 59 seconds."
+                .parse()
+                .unwrap(),
+        );
+        let phrases = analyzer
+            .summary(10)
+            .top_phrases
+            .into_iter()
+            .map(|p| p.term)
+            .collect::<Vec<_>>();
+        assert_eq!(phrases, ["start time"]);
+    }
+
+    #[test]
+    fn analyzer_counts_facilities_and_severities() {
+        let mut analyzer = Analyzer::new();
+        for line in [
+            "<34>Jul  1 09:00:55 host sshd[512]: Failed password",
+            "<38>Jul  1 09:00:56 host sshd[512]: Accepted password",
+            "<6>Jul  1 09:00:57 host kernel[0]: link up",
+            "Jul  1 09:00:58 host kernel[0]: no priority",
+        ] {
+            analyzer.record(&line.parse().unwrap());
+        }
+        let summary = analyzer.summary(3);
+        assert_eq!(
+            summary.by_facility,
+            BTreeMap::from([("auth".to_string(), 2), ("kern".to_string(), 1)])
+        );
+        assert_eq!(
+            summary.by_severity,
+            BTreeMap::from([("crit".to_string(), 1), ("info".to_string(), 2)])
+        );
+    }
+
+    #[test]
+    fn summary_serializes_to_documented_schema() {
+        let summary = Summary {
+            total_entries: 2,
+            by_process: BTreeMap::from([("kernel".to_string(), 2)]),
+            by_hostname: BTreeMap::from([("calvisitor-10-105-160-95".to_string(), 2)]),
+            by_facility: BTreeMap::from([("kern".to_string(), 1)]),
+            by_severity: BTreeMap::from([("notice".to_string(), 1)]),
+            most_frequent_process: "kernel".to_string(),
+            most_frequent_hostname: "calvisitor-10-105-160-95".to_string(),
+            top_keywords: vec![TermCount {
+                term: "arpt".to_string(),
+                count: 2,
+                share: 0.25,
+            }],
+            top_phrases: vec![TermCount {
+                term: "thermal pressure".to_string(),
+                count: 1,
+                share: 0.5,
+            }],
+            process_keywords: BTreeMap::from([(
+                "kernel".to_string(),
+                vec![TermScore {
+                    term: "arpt".to_string(),
+                    count: 2,
+                    score: 1.3863,
+                }],
+            )]),
+            templates: vec![TemplateCount {
+                id: "50f540096b443915".to_string(),
+                template: "ARPT: <*>: wl0: MDNS: IPV4 Addr: <*>".to_string(),
+                count: 2,
+            }],
+            process_templates: BTreeMap::from([(
+                "kernel".to_string(),
+                BTreeMap::from([("50f540096b443915".to_string(), 2)]),
+            )]),
+            rejected_lines: BTreeMap::from([("missing_pid".to_string(), 1)]),
+            continuation_lines: 3,
+            by_file: BTreeMap::from([(
+                "system.log".to_string(),
+                FileTotals {
+                    total_entries: 2,
+                    rejected_lines: BTreeMap::from([("missing_pid".to_string(), 1)]),
+                    continuation_lines: 3,
+                    year: Some(2017),
+                    format: Some("bsd".to_string()),
+                },
+            )]),
+            first_timestamp: Some("2017-07-01T09:00:55".parse().unwrap()),
+            last_timestamp: Some("2017-07-01T09:01:05".parse().unwrap()),
+            first_timestamp_utc: Some("2017-07-01T16:00:55Z".parse().unwrap()),
+            last_timestamp_utc: Some("2017-07-01T16:01:05Z".parse().unwrap()),
+        };
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "total_entries": 2,
+                "by_process": { "kernel": 2 },
+                "by_hostname": { "calvisitor-10-105-160-95": 2 },
+                "by_facility": { "kern": 1 },
+                "by_severity": { "notice": 1 },
+                "most_frequent_process": "kernel",
+                "most_frequent_hostname": "calvisitor-10-105-160-95",
+                "top_keywords": [{ "term": "arpt", "count": 2, "share": 0.25 }],
+                "top_phrases": [{ "term": "thermal pressure", "count": 1, "share": 0.5 }],
+                "process_keywords": {
+                    "kernel": [{ "term": "arpt", "count": 2, "score": 1.3863 }]
+                },
+                "templates": [
+                    { "id": "50f540096b443915", "template": "ARPT: <*>: wl0: MDNS: IPV4 Addr: <*>", "count": 2 }
+                ],
+                "process_templates": { "kernel": { "50f540096b443915": 2 } },
+                "rejected_lines": { "missing_pid": 1 },
+                "continuation_lines": 3,
+                "by_file": {
+                    "system.log": {
+                        "total_entries": 2,
+                        "rejected_lines": { "missing_pid": 1 },
+                        "continuation_lines": 3,
+                        "year": 2017,
+                        "format": "bsd"
+                    }
+                },
+                "first_timestamp": "2017-07-01T09:00:55",
+                "last_timestamp": "2017-07-01T09:01:05",
+                "first_timestamp_utc": "2017-07-01T16:00:55Z",
+                "last_timestamp_utc": "2017-07-01T16:01:05Z"
+            })
+        );
+    }
+}
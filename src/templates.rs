@@ -0,0 +1,642 @@
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::keywords::MIN_HEX_ID_DIGITS;
+
+/// Stands in for a variable token, or a variable part of one, in a template.
+pub const WILDCARD: &str = "<*>";
+
+/// Punctuation that separates the parts of a token masked on their own, so
+/// `Err:-1` becomes `Err:<*>` and `IOThunderboltSwitch<0>(0x0)` becomes
+/// `IOThunderboltSwitch<<*>>(<*>)`.
+const DELIMITERS: [char; 14] = [
+    ':', ';', ',', '=', '(', ')', '[', ']', '{', '}', '<', '>', '"', '\'',
+];
+
+/// Default share of a template's tokens a message must match to join it
+/// rather than start a template of its own.
+const DEFAULT_SIMILARITY: f64 = 0.7;
+
+/// Default number of leading tokens that route a message to its candidate
+/// templates before any similarity is computed.
+const DEFAULT_PREFIX_TOKENS: usize = 4;
+
+/// Routing nodes allowed below each node; further tokens share a wildcard
+/// branch so one chatty process cannot grow the tree without bound.
+const MAX_CHILDREN: usize = 100;
+
+/// Groups messages into event templates online, in the manner of Drain (He
+/// et al., "Drain: An Online Log Parsing Approach with Fixed Depth Tree").
+///
+/// Each message is split at whitespace and its variable tokens (numbers,
+/// hex values, UUIDs and other hex identifiers, MAC and IPv6 addresses,
+/// paths and URLs) are masked as `<*>`. Messages are then routed by token
+/// count and their first few tokens to a handful of templates, and join the
+/// most similar one if enough of its tokens match, with wildcards matching
+/// anything; positions where they differ become `<*>` in the template.
+/// Otherwise the message starts a new template.
+///
+/// A template's [`Template::id`] is a hash of its text, so the same event
+/// gets the same ID in every run, whatever order the inputs are read in.
+#[derive(Debug, Clone)]
+pub struct TemplateMiner {
+    similarity: f64,
+    prefix_tokens: usize,
+    /// Routing trees, one per message length in tokens.
+    by_length: HashMap<usize, Node>,
+    templates: Vec<Template>,
+}
+
+/// A mined template and the number of messages it matched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    tokens: Vec<String>,
+    count: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    children: HashMap<String, Node>,
+    /// Indices into `TemplateMiner::templates` of the templates at a leaf.
+    templates: Vec<usize>,
+}
+
+impl Default for TemplateMiner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TemplateMiner {
+    pub fn new() -> Self {
+        Self {
+            similarity: DEFAULT_SIMILARITY,
+            prefix_tokens: DEFAULT_PREFIX_TOKENS,
+            by_length: HashMap::new(),
+            templates: Vec::new(),
+        }
+    }
+
+    /// Sets the share of a template's tokens, from 0 to 1, that a message
+    /// must match to join it. Higher values give more, narrower templates.
+    pub fn with_similarity(mut self, similarity: f64) -> Self {
+        self.similarity = similarity;
+        self
+    }
+
+    /// Sets how many leading tokens route a message before similarity is
+    /// compared. Messages whose leading constant tokens differ never share
+    /// a template.
+    pub fn with_prefix_tokens(mut self, prefix_tokens: usize) -> Self {
+        self.prefix_tokens = prefix_tokens;
+        self
+    }
+
+    /// Adds a message to the template it matches, or to a new one, and
+    /// returns that template's index in [`TemplateMiner::templates`]. The
+    /// index stays with the template as it generalizes, but depends on the
+    /// order messages arrive in; [`Template::id`] does not.
+    pub fn add(&mut self, message: &str) -> usize {
+        let tokens = message.split_whitespace().map(mask).collect::<Vec<_>>();
+        let mut node = self.by_length.entry(tokens.len()).or_default();
+        for token in tokens.iter().take(self.prefix_tokens) {
+            let key = if token.contains(WILDCARD) || token.bytes().any(|b| b.is_ascii_digit()) {
+                WILDCARD
+            } else {
+                token
+            };
+            let key = if node.children.contains_key(key) {
+                key
+            } else {
+                let key = if node.children.len() < MAX_CHILDREN {
+                    key
+                } else {
+                    WILDCARD
+                };
+                node.children.entry(key.to_string()).or_default();
+                key
+            };
+            node = node.children.get_mut(key).expect("inserted above");
+        }
+
+        let best = node
+            .templates
+            .iter()
+            .map(|&index| (index, self.templates[index].similarity(&tokens)))
+            .filter(|&(_, (similarity, _))| similarity >= self.similarity)
+            // Most similar first, then the most general.
+            .max_by(|(_, a), (_, b)| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)))
+            .map(|(index, _)| index);
+        let index = match best {
+            Some(index) => {
+                self.templates[index].merge(&tokens);
+                index
+            }
+            None => {
+                let index = self.templates.len();
+                self.templates.push(Template {
+                    tokens: tokens.iter().map(|token| token.to_string()).collect(),
+                    count: 0,
+                });
+                node.templates.push(index);
+                index
+            }
+        };
+        self.templates[index].count += 1;
+        index
+    }
+
+    /// All templates, in the order they were first seen.
+    pub fn templates(&self) -> &[Template] {
+        &self.templates
+    }
+
+    /// The template at this index, if one has been mined.
+    pub fn template(&self, index: usize) -> Option<&Template> {
+        self.templates.get(index)
+    }
+}
+
+impl Template {
+    /// The [`template_id`] of the template's current text. It changes while
+    /// the template is still generalizing, so read it once the input is done.
+    pub fn id(&self) -> String {
+        template_id(&self.text())
+    }
+
+    /// Messages matched so far.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The template's tokens joined by single spaces.
+    pub fn text(&self) -> String {
+        self.tokens.join(" ")
+    }
+
+    /// The share of this template's tokens that `tokens` matches, with the
+    /// number of wildcards to prefer general templates on ties. Wildcards
+    /// match anything, so a message that joined a template still matches it
+    /// however far the template generalizes later.
+    fn similarity(&self, tokens: &[Cow<'_, str>]) -> (f64, usize) {
+        if self.tokens.is_empty() {
+            return (1.0, 0);
+        }
+        let mut same = 0;
+        let mut wildcards = 0;
+        for (mine, theirs) in self.tokens.iter().zip(tokens) {
+            if mine == WILDCARD {
+                wildcards += 1;
+            } else if mine == theirs {
+                same += 1;
+            }
+        }
+        let matched = same + wildcards;
+        (matched as f64 / self.tokens.len() as f64, wildcards)
+    }
+
+    fn merge(&mut self, tokens: &[Cow<'_, str>]) {
+        for (mine, theirs) in self.tokens.iter_mut().zip(tokens) {
+            if mine != theirs && mine != WILDCARD {
+                *mine = WILDCARD.to_string();
+            }
+        }
+    }
+}
+
+/// The ID of a template's text: its 64-bit FNV-1a hash as sixteen hex
+/// digits, the same on every platform and run. Templates that end up with the
+/// same text share an ID.
+pub fn template_id(template: &str) -> String {
+    let hash = template
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    format!("{:016x}", hash)
+}
+
+/// The [`template_id`] of each distinct text in `templates`. Should two texts
+/// hash alike, the one that sorts later gets `-2` appended, and so on, so
+/// every text keeps an ID of its own.
+pub fn template_ids<'t>(templates: impl IntoIterator<Item = &'t str>) -> HashMap<&'t str, String> {
+    unique_ids(templates, template_id)
+}
+
+fn unique_ids<'t>(
+    templates: impl IntoIterator<Item = &'t str>,
+    hash: impl Fn(&str) -> String,
+) -> HashMap<&'t str, String> {
+    let mut taken = HashSet::new();
+    templates
+        .into_iter()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|template| {
+            let hash = hash(template);
+            let mut id = hash.clone();
+            for suffix in 2.. {
+                if taken.insert(id.clone()) {
+                    break;
+                }
+                id = format!("{hash}-{suffix}");
+            }
+            (template, id)
+        })
+        .collect()
+}
+
+/// Replaces a whitespace-separated token, or the parts of it between
+/// [`DELIMITERS`], with `<*>` where they are variable.
+fn mask(token: &str) -> Cow<'_, str> {
+    // Every variable part contains a digit or a slash.
+    if !token.bytes().any(|b| b.is_ascii_digit() || b == b'/') {
+        return Cow::Borrowed(token);
+    }
+    if !token.ends_with('.') && is_variable(token) {
+        return Cow::Borrowed(WILDCARD);
+    }
+    let address = token.trim_end_matches([',', '.']);
+    if is_address(address) || is_url(address) {
+        return Cow::Owned(format!("{}{}", WILDCARD, &token[address.len()..]));
+    }
+    let mut masked = String::with_capacity(token.len());
+    let mut start = 0;
+    for (index, delimiter) in token.match_indices(DELIMITERS) {
+        push_masked(&mut masked, &token[start..index]);
+        masked.push_str(delimiter);
+        start = index + delimiter.len();
+    }
+    push_masked(&mut masked, &token[start..]);
+    if masked == token {
+        Cow::Borrowed(token)
+    } else {
+        Cow::Owned(masked)
+    }
+}
+
+/// Pushes `part`, or `<*>` if it is variable. A full stop ending the part is
+/// kept, as it usually ends the sentence rather than the value.
+fn push_masked(masked: &mut String, part: &str) {
+    let value = part.trim_end_matches('.');
+    if is_variable(value) {
+        masked.push_str(WILDCARD);
+        masked.push_str(&part[value.len()..]);
+    } else {
+        masked.push_str(part);
+    }
+}
+
+fn is_variable(part: &str) -> bool {
+    is_number(part) || is_hex(part) || is_hex_id(part) || is_path(part)
+}
+
+/// Integers, decimals and dotted quads, optionally signed: `11`, `-25300`,
+/// `620701.011328`, `10.105.160.95`.
+fn is_number(part: &str) -> bool {
+    let digits = part.strip_prefix(['-', '+']).unwrap_or(part);
+    digits.starts_with(|c: char| c.is_ascii_digit())
+        && digits.bytes().all(|b| b.is_ascii_digit() || b == b'.')
+}
+
+/// `0x`-prefixed hex values such as `0xE0000340`.
+fn is_hex(part: &str) -> bool {
+    part.strip_prefix("0x")
+        .or_else(|| part.strip_prefix("0X"))
+        .is_some_and(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+/// UUIDs and other long hex strings that contain a digit.
+fn is_hex_id(part: &str) -> bool {
+    part.bytes().all(|b| b.is_ascii_hexdigit() || b == b'-')
+        && part.bytes().any(|b| b.is_ascii_digit())
+        && part.bytes().filter(u8::is_ascii_hexdigit).count() >= MIN_HEX_ID_DIGITS
+}
+
+fn is_path(part: &str) -> bool {
+    (part.starts_with('/') || part.starts_with("~/")) && part.len() > 1
+}
+
+/// MAC and IPv6 addresses, which [`DELIMITERS`] would otherwise split at
+/// their colons: `98:01:a7:ee:2c:64`, `fe80::1`.
+fn is_address(token: &str) -> bool {
+    token.bytes().filter(|&b| b == b':').count() >= 2
+        && token.bytes().any(|b| b.is_ascii_digit())
+        && token
+            .bytes()
+            .all(|b| b.is_ascii_hexdigit() || b == b':' || b == b'.')
+}
+
+/// URLs, which [`DELIMITERS`] would otherwise split after their scheme:
+/// `https://p03-caldav.icloud.com/`.
+fn is_url(token: &str) -> bool {
+    token.split_once("://").is_some_and(|(scheme, _)| {
+        !scheme.is_empty() && scheme.bytes().all(|b| b.is_ascii_alphabetic())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LogEntry;
+    use std::collections::HashSet;
+
+    #[test]
+    fn variable_tokens_are_masked() {
+        for (token, masked) in [
+            ("12", "<*>"),
+            ("-25300", "<*>"),
+            ("10.105.160.95", "<*>"),
+            ("0xE0000340", "<*>"),
+            ("messageType", "messageType"),
+            ("wl0", "wl0"),
+            ("en0:", "en0:"),
+            ("5.", "<*>."),
+            ("98:01:a7:ee:2c:64,", "<*>,"),
+            ("Socket:4", "Socket:<*>"),
+            ("path:/var/run/mDNSResponder", "path:<*>"),
+            (
+                "IOThunderboltSwitch<0>(0x0)::listenerCallback",
+                "IOThunderboltSwitch<<*>>(<*>)::listenerCallback",
+            ),
+            ("WWEN[enabled]", "WWEN[enabled]"),
+            ("0x7f9950712080>.", "<*>>."),
+            ("A0C8F9D4-5E3B-4C0A-9F1E-2D7B6A8C4E10", "<*>"),
+            ("deadbeef", "deadbeef"),
+            ("QQ(10018)", "QQ(<*>)"),
+            ("https://p03-caldav.icloud.com/", "<*>"),
+        ] {
+            assert_eq!(mask(token), masked, "{token}");
+        }
+    }
+
+    #[test]
+    fn similar_messages_share_a_template() {
+        let mut miner = TemplateMiner::new();
+        assert_eq!(
+            miner.add("Location icon should now be in state 'Active'"),
+            0
+        );
+        assert_eq!(miner.add("AirPort: Link Up on en0"), 1);
+        assert_eq!(
+            miner.add("Location icon should now be in state 'Inactive'"),
+            0
+        );
+        assert_eq!(miner.add("AirPort: Link Up on awdl0"), 1);
+        assert_eq!(
+            miner.add("AirPort: Link Down on awdl0. Reason 1 (Unspecified)."),
+            2
+        );
+        assert_eq!(
+            miner.template(0).unwrap().text(),
+            "Location icon should now be in state <*>"
+        );
+        assert_eq!(miner.template(1).unwrap().text(), "AirPort: Link Up on <*>");
+        assert_eq!(
+            miner
+                .templates()
+                .iter()
+                .map(Template::count)
+                .collect::<Vec<_>>(),
+            [2, 2, 1]
+        );
+        assert_eq!(miner.template(3), None);
+    }
+
+    #[test]
+    fn template_ids_do_not_depend_on_input_order() {
+        let messages = [
+            "en0: channel changed to 1",
+            "Previous sleep cause: 5",
+            "en0: channel changed to 11",
+            "Previous sleep cause: 3",
+        ];
+        let ids = |messages: &mut dyn Iterator<Item = &&str>| {
+            let mut miner = TemplateMiner::new();
+            messages.for_each(|message| {
+                miner.add(message);
+            });
+            let mut ids = miner
+                .templates()
+                .iter()
+                .map(|template| (template.text(), template.id()))
+                .collect::<Vec<_>>();
+            ids.sort();
+            ids
+        };
+        let forward = ids(&mut messages.iter());
+        assert_eq!(forward, ids(&mut messages.iter().rev()));
+        assert_eq!(
+            forward,
+            [
+                (
+                    "Previous sleep cause: <*>".to_string(),
+                    template_id("Previous sleep cause: <*>")
+                ),
+                (
+                    "en0: channel changed to <*>".to_string(),
+                    template_id("en0: channel changed to <*>")
+                ),
+            ]
+        );
+        assert_eq!(template_id(""), "cbf29ce484222325");
+        assert_eq!(template_id("a"), "af63dc4c8601ec8c");
+    }
+
+    #[test]
+    fn colliding_template_ids_are_told_apart() {
+        let ids = unique_ids(["b", "a", "c", "a"], |_| "f00d".to_string());
+        assert_eq!(
+            ids,
+            HashMap::from([
+                ("a", "f00d".into()),
+                ("b", "f00d-2".into()),
+                ("c", "f00d-3".into())
+            ])
+        );
+        let ids = template_ids(["en0: channel changed to <*>", "Previous sleep cause: <*>"]);
+        assert_eq!(
+            ids["Previous sleep cause: <*>"],
+            template_id("Previous sleep cause: <*>")
+        );
+    }
+
+    #[test]
+    fn messages_with_different_leading_words_do_not_merge() {
+        let mut miner = TemplateMiner::new().with_similarity(0.1);
+        assert_eq!(miner.add("Previous sleep cause: 5"), 0);
+        assert_eq!(miner.add("Previous wake cause: 5"), 1);
+        assert_eq!(
+            miner.template(0).unwrap().text(),
+            "Previous sleep cause: <*>"
+        );
+    }
+
+    /// Templates for common events in `Mac_2k.log`.
+    #[test]
+    fn mac_2k_common_events() {
+        let mut miner = TemplateMiner::new();
+        let mut indices = HashMap::new();
+        for line in include_str!("Mac_2k.log").lines() {
+            let Ok(entry) = line.parse::<LogEntry>() else {
+                continue;
+            };
+            let index = miner.add(&entry.message);
+            indices.entry(entry.message.into_owned()).or_insert(index);
+        }
+        for (message, template) in [
+            (
+                "AppleCamIn::systemWakeCall - messageType = 0xE0000340",
+                "AppleCamIn::systemWakeCall - messageType = <*>",
+            ),
+            (
+                "Thermal pressure state: 1 Memory pressure state: 0",
+                "Thermal pressure state: <*> Memory pressure state: <*>",
+            ),
+            (
+                "IOThunderboltSwitch<0>(0x0)::listenerCallback - Thunderbolt HPD packet for route = 0x0 port = 11 unplug = 0",
+                "IOThunderboltSwitch<<*>>(<*>)::listenerCallback - Thunderbolt HPD packet for route = <*> port = <*> unplug = <*>",
+            ),
+            (
+                "send_datagram_available_ping: pid 445 failed to act on a ping it dequeued before timing out.",
+                "send_datagram_available_ping: pid <*> failed to act on a ping it dequeued before timing out.",
+            ),
+            (
+                "CCFile::captureLogRun() Exiting CCFile::captureLogRun",
+                "CCFile::captureLogRun() Exiting CCFile::captureLogRun",
+            ),
+            ("en0: channel changed to 1", "en0: channel changed to <*>"),
+        ] {
+            let index = indices[message];
+            assert_eq!(miner.template(index).unwrap().text(), template, "{message}");
+        }
+    }
+
+    /// Grouping accuracy over `Mac_2k.log` against hand-labelled events.
+    /// `Mac_2k.log_events.csv` lists templates for the log's common events in
+    /// the `EventId,EventTemplate` layout of Loghub's `*_templates.csv`
+    /// (<https://github.com/logpai/loghub/tree/master/Mac>), so Loghub's own
+    /// file can stand in for it. Each line is labelled with the one template
+    /// it fits, as Loghub's structured logs were, and lines that fit none are
+    /// left out. A labelled line counts as grouped correctly when the miner
+    /// puts exactly the labelled lines of its event in its template, as in
+    /// Loghub's benchmark.
+    #[test]
+    fn mac_2k_grouping_matches_labelled_events() {
+        let mut records = include_str!("Mac_2k.log_events.csv")
+            .lines()
+            .map(csv_fields);
+        let header = records.next().unwrap();
+        let column = |name| header.iter().position(|field| field == name).unwrap();
+        let (event_id, event_template) = (column("EventId"), column("EventTemplate"));
+        let events = records
+            .map(|record| (record[event_id].clone(), words(&record[event_template])))
+            .collect::<Vec<_>>();
+
+        let mut miner = TemplateMiner::new();
+        let mut labelled = Vec::new();
+        for line in include_str!("Mac_2k.log").lines() {
+            let entry = line.parse::<LogEntry>().unwrap();
+            let index = miner.add(&entry.message);
+            let message = words(&entry.message);
+            let mut fitting = events
+                .iter()
+                .filter(|(_, template)| fits(template, &message));
+            if let Some((event, _)) = fitting.next() {
+                assert!(fitting.next().is_none(), "{message} fits several events");
+                labelled.push((event.as_str(), index));
+            }
+        }
+        assert!(labelled.len() >= 1200, "{} lines labelled", labelled.len());
+
+        let mut lines_by_event = HashMap::<&str, HashSet<usize>>::new();
+        let mut lines_by_template = HashMap::<usize, HashSet<usize>>::new();
+        for (line, &(event, template)) in labelled.iter().enumerate() {
+            lines_by_event.entry(event).or_default().insert(line);
+            lines_by_template.entry(template).or_default().insert(line);
+        }
+        let correct: usize = lines_by_event
+            .values()
+            .filter(|lines| {
+                let first = lines.iter().next().unwrap();
+                lines_by_template[&labelled[*first].1] == **lines
+            })
+            .map(HashSet::len)
+            .sum();
+        let accuracy = correct as f64 / labelled.len() as f64;
+        // 0.801 over 1277 labelled lines when this was written.
+        assert!(accuracy >= 0.8, "grouping accuracy {accuracy:.3}");
+    }
+
+    /// `text` with each run of whitespace made a single space, as spacing
+    /// within messages varies more than the templates record.
+    fn words(text: &str) -> String {
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    /// Whether `message` fits `template`, each [`WILDCARD`] standing for any
+    /// text, possibly empty.
+    fn fits(template: &str, message: &str) -> bool {
+        let mut parts = template.split(WILDCARD).collect::<Vec<_>>();
+        let Some(mut rest) = message.strip_prefix(parts.remove(0)) else {
+            return false;
+        };
+        let Some(last) = parts.pop() else {
+            return rest.is_empty();
+        };
+        for part in parts {
+            let Some(start) = rest.find(part) else {
+                return false;
+            };
+            rest = &rest[start + part.len()..];
+        }
+        rest.ends_with(last)
+    }
+
+    #[test]
+    fn templates_fit_messages() {
+        assert!(fits(
+            "en0: channel changed to <*>",
+            "en0: channel changed to 132,+1"
+        ));
+        assert!(fits("<*>: a <*> b", "x: a  b"));
+        assert!(fits(
+            "AppleCamIn::wakeEventHandlerThread",
+            "AppleCamIn::wakeEventHandlerThread"
+        ));
+        assert!(!fits(
+            "AppleCamIn::wakeEventHandlerThread",
+            "AppleCamIn::wakeEventHandlerThreads"
+        ));
+        assert!(!fits("<*> a <*>", "b"));
+        assert!(!fits("a <*> b", "a b"));
+    }
+
+    /// Splits one CSV record, with fields optionally quoted and `""` for a
+    /// quote inside a quoted field.
+    fn csv_fields(record: &str) -> Vec<String> {
+        let mut fields = vec![String::new()];
+        let mut quoted = false;
+        let mut chars = record.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    fields.last_mut().unwrap().push('"');
+                }
+                '"' => quoted = !quoted,
+                ',' if !quoted => fields.push(String::new()),
+                c => fields.last_mut().unwrap().push(c),
+            }
+        }
+        fields
+    }
+
+    #[test]
+    fn csv_fields_unquote() {
+        assert_eq!(
+            csv_fields(r#"1,Jul,"a, ""b""",E1"#),
+            ["1", "Jul", r#"a, "b""#, "E1"]
+        );
+    }
+}
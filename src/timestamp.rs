@@ -0,0 +1,366 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{
+    DateTime, Datelike, Local, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta,
+    TimeZone, Utc,
+};
+use chrono_tz::Tz;
+
+use crate::entry::{LogEntry, Month};
+
+/// Supplies the year BSD syslog timestamps leave out.
+///
+/// The year starts from a hint and moves forward whenever the month jumps
+/// backwards by more than half a year (`Dec 31` followed by `Jan  1`), so a
+/// stream crossing New Year keeps increasing timestamps.
+#[derive(Debug, Clone)]
+pub struct YearResolver {
+    hint: YearHint,
+    current: Option<(i32, Month)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum YearHint {
+    /// The first entry is in this year, e.g. from `--year`.
+    StartingIn(i32),
+    /// The log was last written on this date (file mtime, or today for
+    /// stdin), so its first entry is at most a year earlier.
+    EndingBy(NaiveDate),
+}
+
+impl YearResolver {
+    pub fn starting_in(year: i32) -> Self {
+        Self {
+            hint: YearHint::StartingIn(year),
+            current: None,
+        }
+    }
+
+    pub fn ending_by(date: NaiveDate) -> Self {
+        Self {
+            hint: YearHint::EndingBy(date),
+            current: None,
+        }
+    }
+
+    /// Assumes the log ends today in local time.
+    pub fn ending_today() -> Self {
+        Self::ending_by(chrono::Local::now().date_naive())
+    }
+
+    /// Full timestamp for `entry`, or `None` when its day does not exist in
+    /// the inferred year (`Feb 29` outside a leap year, `Apr 31`).
+    pub fn resolve(&mut self, entry: &LogEntry<'_>) -> Option<NaiveDateTime> {
+        let year = self.year_for(entry.month);
+        let date = NaiveDate::from_ymd_opt(year, entry.month.number(), entry.day.into())?;
+        let time = NaiveTime::from_hms_opt(
+            entry.time.hour.into(),
+            entry.time.minute.into(),
+            entry.time.second.into(),
+        )?;
+        Some(date.and_time(time))
+    }
+
+    fn year_for(&mut self, month: Month) -> i32 {
+        let year = match self.current {
+            None => match self.hint {
+                YearHint::StartingIn(year) => year,
+                YearHint::EndingBy(date) if month.number() > date.month() => date.year() - 1,
+                YearHint::EndingBy(date) => date.year(),
+            },
+            Some((year, previous)) if previous.number() > month.number() + 6 => year + 1,
+            Some((year, previous)) if month.number() > previous.number() + 6 => year - 1,
+            Some((year, _)) => year,
+        };
+        self.current = Some((year, month));
+        year
+    }
+}
+
+/// The zone a log's local timestamps were written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Zone {
+    /// This machine's zone.
+    Local,
+    Named(Tz),
+}
+
+impl FromStr for Zone {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if name.eq_ignore_ascii_case("local") {
+            Ok(Zone::Local)
+        } else {
+            name.parse::<Tz>()
+                .map(Zone::Named)
+                .map_err(|_| format!("unknown time zone: {}", name))
+        }
+    }
+}
+
+impl fmt::Display for Zone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Zone::Local => f.write_str("local"),
+            Zone::Named(tz) => f.write_str(tz.name()),
+        }
+    }
+}
+
+impl Zone {
+    fn utc_candidates(self, local: &NaiveDateTime) -> LocalResult<DateTime<Utc>> {
+        match self {
+            Zone::Local => Local
+                .from_local_datetime(local)
+                .map(|t| t.with_timezone(&Utc)),
+            Zone::Named(tz) => tz.from_local_datetime(local).map(|t| t.with_timezone(&Utc)),
+        }
+    }
+}
+
+/// Parses a host-to-zone mapping file: one `<hostname> <zone>` pair per
+/// line, with blank lines and `#` comments ignored.
+///
+/// ```text
+/// # Build farm in Frankfurt, laptops in California
+/// buildbox-01              Europe/Berlin
+/// calvisitor-10-105-160-95 America/Los_Angeles
+/// ```
+pub fn parse_host_zones(text: &str) -> Result<HashMap<String, Zone>, String> {
+    let mut zones = HashMap::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let (Some(host), Some(zone), None) = (fields.next(), fields.next(), fields.next()) else {
+            return Err(format!("line {}: expected <hostname> <zone>", index + 1));
+        };
+        let zone = zone
+            .parse::<Zone>()
+            .map_err(|err| format!("line {}: {}", index + 1, err))?;
+        zones.insert(host.to_string(), zone);
+    }
+    Ok(zones)
+}
+
+/// Converts local timestamps to UTC using each host's zone.
+///
+/// Local times skipped by a DST change (the spring-forward gap) are read with
+/// the offset in force just before it, as a clock that missed the change
+/// would have written them. Local times that occur twice (the fall-back
+/// overlap) take the first occurrence until the host's entries have moved
+/// past it, and the second one after that.
+#[derive(Debug, Clone)]
+pub struct ZoneConverter {
+    default: Zone,
+    by_host: HashMap<String, Zone>,
+    last_seen: HashMap<String, DateTime<Utc>>,
+}
+
+impl ZoneConverter {
+    pub fn new(default: Zone) -> Self {
+        Self {
+            default,
+            by_host: HashMap::new(),
+            last_seen: HashMap::new(),
+        }
+    }
+
+    /// Zones for individual hosts, overriding the default.
+    pub fn with_host_zones(mut self, by_host: HashMap<String, Zone>) -> Self {
+        self.by_host = by_host;
+        self
+    }
+
+    pub fn zone_for(&self, hostname: &str) -> Zone {
+        self.by_host.get(hostname).copied().unwrap_or(self.default)
+    }
+
+    pub fn to_utc(&mut self, hostname: &str, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+        let zone = self.zone_for(hostname);
+        let utc = match zone.utc_candidates(&local) {
+            LocalResult::Single(utc) => utc,
+            LocalResult::Ambiguous(earlier, later) => match self.last_seen.get(hostname) {
+                Some(last) if *last > earlier => later,
+                _ => earlier,
+            },
+            LocalResult::None => {
+                let before_gap = local - TimeDelta::hours(1);
+                zone.utc_candidates(&before_gap).earliest()? + TimeDelta::hours(1)
+            }
+        };
+        match self.last_seen.get_mut(hostname) {
+            Some(last) => *last = utc,
+            None => {
+                self.last_seen.insert(hostname.to_string(), utc);
+            }
+        }
+        Some(utc)
+    }
+
+    /// The wall-clock time `hostname`'s zone showed at `utc`, for formats
+    /// that record an instant rather than a local time.
+    pub fn to_local(&self, hostname: &str, utc: DateTime<Utc>) -> NaiveDateTime {
+        match self.zone_for(hostname) {
+            Zone::Local => utc.with_timezone(&Local).naive_local(),
+            Zone::Named(tz) => utc.with_timezone(&tz).naive_local(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(line: &str) -> LogEntry<'static> {
+        line.parse().unwrap()
+    }
+
+    fn ymd_hms(y: i32, m: u32, d: u32, h: u32, min: u32, s: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_hms_opt(h, min, s)
+            .unwrap()
+    }
+
+    #[test]
+    fn explicit_year_rolls_over_at_new_year() {
+        let mut years = YearResolver::starting_in(2016);
+        assert_eq!(
+            years.resolve(&entry("Dec 31 23:59:58 host kernel[0]: a")),
+            Some(ymd_hms(2016, 12, 31, 23, 59, 58))
+        );
+        assert_eq!(
+            years.resolve(&entry("Jan  1 00:00:01 host kernel[0]: b")),
+            Some(ymd_hms(2017, 1, 1, 0, 0, 1))
+        );
+        assert_eq!(
+            years.resolve(&entry("Jan  1 00:00:02 host kernel[0]: c")),
+            Some(ymd_hms(2017, 1, 1, 0, 0, 2))
+        );
+    }
+
+    #[test]
+    fn slightly_out_of_order_lines_keep_the_year() {
+        let mut years = YearResolver::starting_in(2017);
+        years.resolve(&entry("Jul  1 09:00:00 host kernel[0]: a"));
+        assert_eq!(
+            years.resolve(&entry("Jun 30 23:59:59 host kernel[0]: b")),
+            Some(ymd_hms(2017, 6, 30, 23, 59, 59))
+        );
+    }
+
+    #[test]
+    fn ending_date_puts_later_months_in_the_previous_year() {
+        let modified = NaiveDate::from_ymd_opt(2018, 1, 3).unwrap();
+        let mut years = YearResolver::ending_by(modified);
+        assert_eq!(
+            years.resolve(&entry("Dec 30 10:00:00 host kernel[0]: a")),
+            Some(ymd_hms(2017, 12, 30, 10, 0, 0))
+        );
+        assert_eq!(
+            years.resolve(&entry("Jan  2 10:00:00 host kernel[0]: b")),
+            Some(ymd_hms(2018, 1, 2, 10, 0, 0))
+        );
+
+        let mut years = YearResolver::ending_by(modified);
+        assert_eq!(
+            years.resolve(&entry("Jan  2 10:00:00 host kernel[0]: b")),
+            Some(ymd_hms(2018, 1, 2, 10, 0, 0))
+        );
+    }
+
+    #[test]
+    fn impossible_dates_have_no_timestamp() {
+        let mut years = YearResolver::starting_in(2017);
+        assert_eq!(
+            years.resolve(&entry("Feb 29 10:00:00 host kernel[0]: a")),
+            None
+        );
+        let mut years = YearResolver::starting_in(2016);
+        assert!(
+            years
+                .resolve(&entry("Feb 29 10:00:00 host kernel[0]: a"))
+                .is_some()
+        );
+    }
+
+    fn utc(text: &str) -> Option<DateTime<Utc>> {
+        Some(text.parse().unwrap())
+    }
+
+    #[test]
+    fn convert_named_zone_to_utc() {
+        let mut zones = ZoneConverter::new("America/Los_Angeles".parse().unwrap());
+        assert_eq!(
+            zones.to_utc("host", ymd_hms(2017, 7, 1, 9, 0, 55)),
+            utc("2017-07-01T16:00:55Z")
+        );
+        assert_eq!(
+            zones.to_utc("host", ymd_hms(2017, 1, 1, 9, 0, 55)),
+            utc("2017-01-01T17:00:55Z")
+        );
+    }
+
+    #[test]
+    fn host_zones_override_the_default() {
+        let by_host =
+            parse_host_zones("# comment\n\nbuildbox-01  Europe/Berlin # Frankfurt\nlaptop UTC\n")
+                .unwrap();
+        let mut zones =
+            ZoneConverter::new("America/Los_Angeles".parse().unwrap()).with_host_zones(by_host);
+        assert_eq!(
+            zones.to_utc("buildbox-01", ymd_hms(2017, 7, 1, 9, 0, 0)),
+            utc("2017-07-01T07:00:00Z")
+        );
+        assert_eq!(
+            zones.to_utc("laptop", ymd_hms(2017, 7, 1, 9, 0, 0)),
+            utc("2017-07-01T09:00:00Z")
+        );
+        assert!(parse_host_zones("host Mars/Olympus_Mons").is_err());
+        assert!(parse_host_zones("host").is_err());
+    }
+
+    #[test]
+    fn convert_utc_to_the_host_zone() {
+        let zones = ZoneConverter::new("America/Los_Angeles".parse().unwrap())
+            .with_host_zones(parse_host_zones("buildbox-01 Europe/Berlin").unwrap());
+        let utc = "2024-07-01T09:00:55Z".parse().unwrap();
+        assert_eq!(zones.to_local("laptop", utc), ymd_hms(2024, 7, 1, 2, 0, 55));
+        assert_eq!(
+            zones.to_local("buildbox-01", utc),
+            ymd_hms(2024, 7, 1, 11, 0, 55)
+        );
+    }
+
+    #[test]
+    fn spring_forward_gap_uses_the_offset_before_it() {
+        // 2017-03-12 02:00 PST jumped to 03:00 PDT.
+        let mut zones = ZoneConverter::new("America/Los_Angeles".parse().unwrap());
+        assert_eq!(
+            zones.to_utc("host", ymd_hms(2017, 3, 12, 2, 30, 0)),
+            utc("2017-03-12T10:30:00Z")
+        );
+    }
+
+    #[test]
+    fn fall_back_overlap_follows_the_stream() {
+        // 2017-11-05 02:00 PDT fell back to 01:00 PST, so 01:xx happened twice.
+        let mut zones = ZoneConverter::new("America/Los_Angeles".parse().unwrap());
+        let stream = [
+            (ymd_hms(2017, 11, 5, 1, 10, 0), "2017-11-05T08:10:00Z"),
+            (ymd_hms(2017, 11, 5, 1, 50, 0), "2017-11-05T08:50:00Z"),
+            (ymd_hms(2017, 11, 5, 1, 5, 0), "2017-11-05T09:05:00Z"),
+            (ymd_hms(2017, 11, 5, 1, 55, 0), "2017-11-05T09:55:00Z"),
+            (ymd_hms(2017, 11, 5, 2, 5, 0), "2017-11-05T10:05:00Z"),
+        ];
+        for (local, expected) in stream {
+            assert_eq!(zones.to_utc("host", local), utc(expected));
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::path::Path;
+
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::entry::{LogEntry, Month, TimeOfDay};
+use crate::error::{ParseError, ParseErrorKind};
+
+/// Fields of an Apple unified log event that have no BSD counterpart.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnifiedFields {
+    /// Full path of the logging executable; [`LogEntry::process`] holds its
+    /// file name.
+    pub process_image_path: String,
+    pub subsystem: Option<String>,
+    pub category: Option<String>,
+    /// `Default`, `Info`, `Debug`, `Error` or `Fault`; `None` for activities
+    /// and other events that are not log messages.
+    pub message_type: Option<String>,
+}
+
+/// The keys of a `log show --style ndjson` event that the analyzer uses.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Event {
+    timestamp: Option<String>,
+    #[serde(default)]
+    process_image_path: String,
+    #[serde(rename = "processID", default)]
+    process_id: u32,
+    subsystem: Option<String>,
+    category: Option<String>,
+    message_type: Option<String>,
+    #[serde(default)]
+    event_message: String,
+}
+
+/// Parses one event of `log show --style ndjson` output:
+///
+/// ```text
+/// {"timestamp":"2024-07-01 09:00:55.123456-0700","processImagePath":"/usr/libexec/configd","processID":43,"subsystem":"com.apple.SystemConfiguration","category":"IPMonitor","messageType":"Default","eventMessage":"network changed",...}
+/// ```
+///
+/// The process is the file name of `processImagePath` and the pid is
+/// `processID`. Unified log events carry no hostname, so it is recorded as
+/// `-`. As with RFC 5424, the timestamp has its own offset, so `timestamp`
+/// and `timestamp_utc` are filled in directly. Objects without a timestamp,
+/// such as the `{"count":...,"finished":1}` trailer `log show` writes last,
+/// are rejected.
+pub fn parse_unified(line: &str) -> Result<LogEntry<'static>, ParseError> {
+    let event: Event = serde_json::from_str(line).map_err(ParseError::from_json)?;
+    let timestamp_str = event
+        .timestamp
+        .ok_or_else(|| ParseError::new(ParseErrorKind::MissingTimestamp, 0))?;
+    let timestamp =
+        DateTime::parse_from_str(&timestamp_str, "%Y-%m-%d %H:%M:%S%.f%z").map_err(|_| {
+            let offset = line.find(&timestamp_str).unwrap_or(0);
+            ParseError::new(
+                ParseErrorKind::InvalidTimestamp(timestamp_str.clone()),
+                offset,
+            )
+        })?;
+    let process = Path::new(&event.process_image_path)
+        .file_name()
+        .map_or_else(
+            || "-".to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+
+    let local = timestamp.naive_local();
+    Ok(LogEntry {
+        month: Month::ALL[local.month0() as usize],
+        day: local.day() as u8,
+        time: TimeOfDay {
+            hour: local.hour() as u8,
+            minute: local.minute() as u8,
+            second: local.second() as u8,
+        },
+        hostname: "-".into(),
+        process: process.into(),
+        pid: event.process_id,
+        message: event.event_message.into(),
+        timestamp: Some(local),
+        timestamp_utc: Some(timestamp.with_timezone(&Utc)),
+        priority: None,
+        rfc5424: None,
+        unified: Some(UnifiedFields {
+            process_image_path: event.process_image_path,
+            subsystem: non_empty(event.subsystem),
+            category: non_empty(event.category),
+            message_type: non_empty(event.message_type),
+        }),
+    })
+}
+
+/// `log show` writes absent subsystems and categories as empty strings.
+fn non_empty(field: Option<String>) -> Option<String> {
+    field.filter(|field| !field.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENT: &str = r#"{"traceID":1234,"eventMessage":"network changed: v4(en0+:10.105.160.95)","eventType":"logEvent","source":null,"formatString":"network changed: %@","activityIdentifier":0,"subsystem":"com.apple.SystemConfiguration","category":"IPMonitor","threadID":5821,"senderImageUUID":"0A1B","bootUUID":"","processImagePath":"\/usr\/libexec\/configd","timestamp":"2024-07-01 09:00:55.123456-0700","senderImagePath":"\/usr\/libexec\/configd","machTimestamp":9876,"messageType":"Default","processImageUUID":"0A1B","processID":43,"senderProgramCounter":1,"parentActivityIdentifier":0,"timezoneName":""}"#;
+
+    #[test]
+    fn parse_ndjson_event() {
+        let entry = parse_unified(EVENT).unwrap();
+        assert_eq!(entry.month, Month::Jul);
+        assert_eq!(entry.day, 1);
+        assert_eq!(entry.time.to_string(), "09:00:55");
+        assert_eq!(entry.hostname, "-");
+        assert_eq!(entry.process, "configd");
+        assert_eq!(entry.pid, 43);
+        assert_eq!(entry.message, "network changed: v4(en0+:10.105.160.95)");
+        assert_eq!(
+            entry.timestamp_utc.unwrap().to_rfc3339(),
+            "2024-07-01T16:00:55.123456+00:00"
+        );
+        let fields = entry.unified.unwrap();
+        assert_eq!(fields.process_image_path, "/usr/libexec/configd");
+        assert_eq!(
+            fields.subsystem.as_deref(),
+            Some("com.apple.SystemConfiguration")
+        );
+        assert_eq!(fields.category.as_deref(), Some("IPMonitor"));
+        assert_eq!(fields.message_type.as_deref(), Some("Default"));
+    }
+
+    #[test]
+    fn missing_fields_fall_back() {
+        let entry = parse_unified(
+            r#"{"timestamp":"2024-07-01 09:00:55.000000+0200","subsystem":"","eventType":"activityCreateEvent"}"#,
+        )
+        .unwrap();
+        assert_eq!(entry.process, "-");
+        assert_eq!(entry.pid, 0);
+        assert_eq!(entry.message, "");
+        let fields = entry.unified.unwrap();
+        assert_eq!(fields.subsystem, None);
+        assert_eq!(fields.message_type, None);
+    }
+
+    #[test]
+    fn rejected_unified_lines() {
+        let kind = |line: &str| parse_unified(line).unwrap_err().kind;
+        assert_eq!(
+            kind(r#"{"count":2,"finished":1}"#),
+            ParseErrorKind::MissingTimestamp
+        );
+        assert_eq!(
+            kind(r#"{"timestamp":"Jul  1 09:00:55"}"#),
+            ParseErrorKind::InvalidTimestamp("Jul  1 09:00:55".to_string())
+        );
+        assert_eq!(
+            kind("Jul  1 09:00:55 host kernel[0]: x").name(),
+            "invalid_json"
+        );
+        let err = parse_unified(r#"{"timestamp":"2024-07-01 09:00:55-0700","processID":"x"}"#)
+            .unwrap_err();
+        assert_eq!(err.kind.name(), "invalid_json");
+        assert_eq!(err.offset, 54);
+    }
+}